@@ -0,0 +1,248 @@
+use std::{collections::{HashMap, HashSet}, fmt::{self, Display}, ops::RangeInclusive, rc::Rc};
+
+use super::{func::function_registry::{FunctionRegistry, ParamCount, RuntimeType}, parser::{arity, ASTNode}};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Walks an AST without executing it, reporting problems that are knowable
+/// statically: unknown functions, wrong arity and literal operands of the
+/// wrong type.
+pub struct Checker<'a> {
+    fr: &'a FunctionRegistry,
+    functions: HashMap<String, RangeInclusive<usize>>,
+    variables: HashSet<String>,
+    diagnostics: Vec<Diagnostic>
+}
+
+impl<'a> Checker<'a> {
+    /// `functions` maps the script-defined functions already known to the
+    /// interpreter to the range of argument counts they accept.
+    pub fn new(fr: &'a FunctionRegistry, functions: HashMap<String, RangeInclusive<usize>>) -> Self {
+        Self { fr, functions, variables: HashSet::new(), diagnostics: vec![] }
+    }
+
+    pub fn check(mut self, ast: &[Rc<ASTNode>]) -> Vec<Diagnostic> {
+        for node in ast {
+            self.collect_functions(node);
+        }
+        for node in ast {
+            self.check_node(node);
+        }
+        self.diagnostics
+    }
+
+    fn report(&mut self, message: String) {
+        self.diagnostics.push(Diagnostic { message });
+    }
+
+    fn collect_functions(&mut self, node: &ASTNode) {
+        match node {
+            ASTNode::FunctionDeclaration { name, params, body } => {
+                self.functions.insert(name.to_owned(), arity(params));
+                self.variables.extend(params.iter().flat_map(|param| param.pattern.names()).map(str::to_owned));
+                body.iter().for_each(|node| self.collect_functions(node));
+            },
+            ASTNode::VarDeclaration { pattern, .. } => self.variables.extend(pattern.names().into_iter().map(str::to_owned)),
+            ASTNode::IfStmt { true_block, false_block, .. } => {
+                true_block.iter().for_each(|node| self.collect_functions(node));
+                false_block.iter().flatten().for_each(|node| self.collect_functions(node));
+            },
+            ASTNode::WhileStmt { body, .. } | ASTNode::DoWhileStmt { body, .. } => body.iter().for_each(|node| self.collect_functions(node)),
+            ASTNode::ForStmt { init, body, .. } => init.iter().chain(body).for_each(|node| self.collect_functions(node)),
+            ASTNode::TryCatch { try_block, catch_block, .. } => {
+                try_block.iter().chain(catch_block).for_each(|node| self.collect_functions(node));
+            },
+            _ => {}
+        }
+    }
+
+    fn check_node(&mut self, node: &ASTNode) {
+        match node {
+            ASTNode::Number(..) | ASTNode::Bool(..) | ASTNode::Null | ASTNode::String(..) | ASTNode::Identifier { .. }
+            | ASTNode::Break { .. } | ASTNode::Continue { .. } => {},
+            ASTNode::ArrayLiteral { elements } => self.check_all(elements),
+            ASTNode::ObjectLiteral { entries } => entries.iter().for_each(|(_, value)| self.check_node(value)),
+            ASTNode::FunctionCall { name, args } => {
+                self.check_call(name, args);
+                self.check_all(args);
+            },
+            ASTNode::MethodCall { receiver, args, .. } => {
+                self.check_node(receiver);
+                self.check_all(args);
+            },
+            ASTNode::Index { target, index } => {
+                if let Some(found) = literal_type(index) && found != RuntimeType::Number {
+                    self.report(format!("Index must be a Number, got {:?}", found));
+                }
+                self.check_node(target);
+                self.check_node(index);
+            },
+            ASTNode::BinaryExpression { left, right, operator } => {
+                self.check_operands(left, right, operator);
+                self.check_node(left);
+                self.check_node(right);
+            },
+            ASTNode::UnaryExpression { expr, .. } => {
+                if let Some(found) = literal_type(expr) && found != RuntimeType::Number {
+                    self.report(format!("Cannot apply a sign to a {:?}", found));
+                }
+                self.check_node(expr);
+            },
+            ASTNode::ConditionalExpression { condition, when_true, when_false } => {
+                self.check_node(condition);
+                self.check_node(when_true);
+                self.check_node(when_false);
+            },
+            ASTNode::Spread { expr } => {
+                if let Some(found) = literal_type(expr) && found != RuntimeType::Array {
+                    self.report(format!("Cannot spread a {:?}, expected an Array", found));
+                }
+                self.check_node(expr);
+            },
+            ASTNode::VarDeclaration { value, .. } | ASTNode::VarAssignment { value, .. } => self.check_node(value),
+            ASTNode::IfStmt { expr, true_block, false_block } => {
+                if let Some(found) = literal_type(expr) && found != RuntimeType::Bool {
+                    self.report(format!("Expression inside if must return a bool value, got {:?}", found));
+                }
+                self.check_node(expr);
+                self.check_all(true_block);
+                if let Some(false_block) = false_block {
+                    self.check_all(false_block);
+                }
+            },
+            ASTNode::WhileStmt { condition, body, .. } => {
+                if let Some(found) = literal_type(condition) && found != RuntimeType::Bool {
+                    self.report(format!("Expression inside while must return a bool value, got {:?}", found));
+                }
+                self.check_node(condition);
+                self.check_all(body);
+            },
+            ASTNode::ForStmt { init, condition, update, body, .. } => {
+                if let Some(condition) = condition {
+                    if let Some(found) = literal_type(condition) && found != RuntimeType::Bool {
+                        self.report(format!("Expression inside for must return a bool value, got {:?}", found));
+                    }
+                    self.check_node(condition);
+                }
+                init.iter().chain(update).for_each(|node| self.check_node(node));
+                self.check_all(body);
+            },
+            ASTNode::DoWhileStmt { body, condition, .. } => {
+                if let Some(found) = literal_type(condition) && found != RuntimeType::Bool {
+                    self.report(format!("Expression inside do-while must return a bool value, got {:?}", found));
+                }
+                self.check_all(body);
+                self.check_node(condition);
+            },
+            ASTNode::TryCatch { try_block, catch_block, .. } => {
+                self.check_all(try_block);
+                self.check_all(catch_block);
+            },
+            ASTNode::FunctionDeclaration { params, body, .. } => {
+                params.iter().filter_map(|param| param.default.as_ref()).for_each(|default| self.check_node(default));
+                self.check_all(body);
+            },
+            ASTNode::Throw { value } => self.check_node(value),
+            ASTNode::ReturnStmt { value } => {
+                if let Some(value) = value {
+                    self.check_node(value);
+                }
+            }
+        }
+    }
+
+    fn check_all(&mut self, nodes: &[Rc<ASTNode>]) {
+        nodes.iter().for_each(|node| self.check_node(node));
+    }
+
+    fn check_call(&mut self, name: &str, args: &[Rc<ASTNode>]) {
+        // the number of arguments a spread expands to is only known at runtime
+        let has_spread = args.iter().any(|arg| matches!(arg.as_ref(), ASTNode::Spread { .. }));
+        if let Some(arity) = self.functions.get(name) {
+            if !has_spread && !arity.contains(&args.len()) {
+                let message = if arity.start() == arity.end() {
+                    format!("Function {} expects {} params, got {}", name, arity.start(), args.len())
+                } else {
+                    format!("Function {} expects {} to {} params, got {}", name, arity.start(), arity.end(), args.len())
+                };
+                self.report(message);
+            }
+            return;
+        }
+        let Some(signature) = self.fr.signature(name) else {
+            // a variable may hold a function value, which is only known at runtime
+            if !self.variables.contains(name) {
+                self.report(format!("Function {} does not exist", name));
+            }
+            return;
+        };
+        if has_spread {
+            return;
+        }
+        match signature.param_count {
+            ParamCount::Fixed(num) if num != args.len() => {
+                self.report(format!("Function {} expects {} params, got {}", name, num, args.len()));
+                return;
+            },
+            ParamCount::Dynamic(min) if args.len() < min => {
+                self.report(format!("Function {} expects {} params as minimum, got {}", name, min, args.len()));
+                return;
+            },
+            _ => {}
+        }
+        for (i, arg) in args.iter().enumerate() {
+            let expected = &signature.param_types[i.min(signature.param_types.len() - 1)];
+            if let Some(found) = literal_type(arg) && *expected != RuntimeType::Any && *expected != found {
+                self.report(format!("Param {} of function {} expected {:?}, got {:?}", i, name, expected, found));
+            }
+        }
+    }
+
+    fn check_operands(&mut self, left: &ASTNode, right: &ASTNode, operator: &str) {
+        let (Some(left), Some(right)) = (literal_type(left), literal_type(right)) else {
+            return;
+        };
+        let valid = match operator {
+            "+" => matches!(
+                (&left, &right),
+                (RuntimeType::Number | RuntimeType::String, RuntimeType::Number | RuntimeType::String)
+            ),
+            "*" => matches!(
+                (&left, &right),
+                (RuntimeType::Number, RuntimeType::Number | RuntimeType::String) | (RuntimeType::String, RuntimeType::Number)
+            ),
+            ">" | "<" | ">=" | "<=" => matches!(
+                (&left, &right),
+                (RuntimeType::Number, RuntimeType::Number) | (RuntimeType::String, RuntimeType::String)
+            ),
+            "==" | "!=" | "??" => true,
+            "&&" | "||" => left == RuntimeType::Bool && right == RuntimeType::Bool,
+            _ => left == RuntimeType::Number && right == RuntimeType::Number
+        };
+        if !valid {
+            self.report(format!("Operator {} cannot be applied to {:?} and {:?}", operator, left, right));
+        }
+    }
+}
+
+/// The type of `node` when it is a literal, `None` when it needs evaluating.
+fn literal_type(node: &ASTNode) -> Option<RuntimeType> {
+    match node {
+        ASTNode::Number(..) => Some(RuntimeType::Number),
+        ASTNode::String(..) => Some(RuntimeType::String),
+        ASTNode::Bool(..) => Some(RuntimeType::Bool),
+        ASTNode::Null => Some(RuntimeType::Null),
+        ASTNode::ArrayLiteral { .. } => Some(RuntimeType::Array),
+        ASTNode::ObjectLiteral { .. } => Some(RuntimeType::Object),
+        _ => None
+    }
+}
@@ -1,6 +1,6 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, mem};
 
-use super::{interpreter::RuntimeValue};
+use super::{error::RuntimeError, interpreter::RuntimeValue, panics::runtime_error};
 
 #[derive(Debug)]
 pub struct Env {
@@ -17,25 +17,108 @@ impl Env {
 
     pub fn add(&mut self, var_name: String, value: RuntimeValue) {
         if self.vars.contains_key(&var_name) {
-            panic!("Variable {} was already declared", var_name)
+            runtime_error(RuntimeError::AlreadyDeclared(var_name));
         }
         self.vars.insert(var_name, value);
     }
 
     pub fn update(&mut self, var_name: String, value: RuntimeValue) {
-        if !self.vars.contains_key(&var_name) {
-            panic!("Variable {} does not exist", var_name);
+        let mut current = Some(self);
+        while let Some(c) = current {
+            if let Some(variable) = c.vars.get_mut(&var_name) {
+                *variable = value;
+                return;
+            }
+            current = c.parent.as_deref_mut();
         }
-        self.vars.insert(var_name, value);
+        panic!("Variable {} does not exist", var_name);
+    }
+
+    pub fn push_scope(&mut self) {
+        let parent = mem::replace(self, Env::new(None));
+        self.parent = Some(Box::new(parent));
+    }
+
+    pub fn pop_scope(&mut self) {
+        let parent = self.parent.take().expect("Cannot pop the global scope");
+        *self = *parent;
+    }
+
+    /// Number of scopes above the global one.
+    pub fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut current = self.parent.as_deref();
+        while let Some(c) = current {
+            depth += 1;
+            current = c.parent.as_deref();
+        }
+        depth
+    }
+
+    /// Detaches every local scope, leaving only the global one. The detached
+    /// scopes are returned innermost first so they can be restored later.
+    pub fn detach_locals(&mut self) -> Vec<HashMap<String, RuntimeValue>> {
+        let mut locals = vec![];
+        while self.parent.is_some() {
+            locals.push(mem::take(&mut self.vars));
+            self.pop_scope();
+        }
+        locals
+    }
+
+    pub fn restore_locals(&mut self, locals: Vec<HashMap<String, RuntimeValue>>) {
+        for vars in locals.into_iter().rev() {
+            self.push_scope();
+            self.vars = vars;
+        }
+    }
+
+    pub fn is_declared(&self, var_name: &str) -> bool {
+        let mut current = Some(self);
+        while let Some(c) = current {
+            if c.vars.contains_key(var_name) {
+                return true;
+            }
+            current = c.parent.as_deref();
+        }
+        false
+    }
+
+    /// Every variable visible from the current scope, innermost first.
+    pub fn names(&self) -> Vec<&String> {
+        let mut names: Vec<&String> = vec![];
+        let mut current = Some(self);
+        while let Some(c) = current {
+            for name in c.vars.keys() {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+            current = c.parent.as_deref();
+        }
+        names
+    }
+
+    /// The variables of every scope sorted by name, innermost scope first and
+    /// the global one last.
+    pub fn scopes(&self) -> Vec<Vec<(&String, &RuntimeValue)>> {
+        let mut scopes = vec![];
+        let mut current = Some(self);
+        while let Some(c) = current {
+            let mut vars: Vec<(&String, &RuntimeValue)> = c.vars.iter().collect();
+            vars.sort_by_key(|(name, _)| *name);
+            scopes.push(vars);
+            current = c.parent.as_deref();
+        }
+        scopes
     }
 
     pub fn get(&self, var_name: &str) -> &RuntimeValue {
         let mut current = Some(self);
         while current.is_some() {
             let c = current.unwrap();
-            let variable = c.vars.get(var_name);
-            if variable.is_some() {
-                return variable.unwrap();
+            if let Some(variable) = c.vars.get(var_name) {
+                return variable;
             }
             current = c.parent.as_deref();
         }
@@ -45,12 +128,14 @@ impl Env {
 
 #[cfg(test)]
 mod test {
+    use crate::lang::panics::catch;
+
     use super::*;
     #[test]
     fn test_environment() {
         let mut env1 = Env::new(None);
         env1.add("x".to_string(), RuntimeValue::Number(5.));
-        env1.add("y".to_string(), RuntimeValue::String("simon".to_string()));
+        env1.add("y".to_string(), RuntimeValue::String("simon".into()));
 
         let mut env2 = Env::new(Some(env1));
         env2.add("z".to_string(), RuntimeValue::Bool(false));
@@ -68,11 +153,24 @@ mod test {
     fn test_panic_environment() {
         let mut env1 = Env::new(None);
         env1.add("x".to_string(), RuntimeValue::Number(5.));
-        env1.add("y".to_string(), RuntimeValue::String("simon".to_string()));
+        env1.add("y".to_string(), RuntimeValue::String("simon".into()));
 
         let mut env2 = Env::new(Some(env1));
         env2.add("z".to_string(), RuntimeValue::Bool(false));
 
         env2.get("a");
     }
+
+    #[test]
+    fn test_redeclaration() {
+        let mut env1 = Env::new(None);
+        env1.add("x".to_string(), RuntimeValue::Number(5.));
+        let result = catch(|| env1.add("x".to_string(), RuntimeValue::Number(6.)));
+        assert_eq!(result, Err(RuntimeError::AlreadyDeclared("x".to_string())));
+
+        let mut env2 = Env::new(Some(env1));
+        env2.add("x".to_string(), RuntimeValue::Number(7.));
+        assert!(env2.is_declared("x"));
+        assert_eq!(*env2.get("x"), RuntimeValue::Number(7.));
+    }
 }
\ No newline at end of file
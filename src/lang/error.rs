@@ -0,0 +1,148 @@
+use std::fmt::{self, Display};
+
+use super::func::function_registry::RuntimeType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    TypeMismatch {
+        expected: RuntimeType,
+        found: RuntimeType
+    },
+    AlreadyDeclared(String),
+    StepLimitExceeded(usize),
+    OutputLimitExceeded(usize),
+    StringTooLong {
+        length: usize,
+        max: usize
+    },
+    /// Raised by a `throw` statement, holding the thrown value as text. The
+    /// value itself stays with the interpreter until a `catch` takes it.
+    Thrown(String),
+    /// Raised while reading the source, with the character index it points at.
+    Syntax {
+        message: String,
+        pos: usize
+    },
+    Message(String)
+}
+
+impl RuntimeError {
+    /// The character index in the source the error points at, if it has one.
+    pub fn pos(&self) -> Option<usize> {
+        match self {
+            Self::Syntax { pos, .. } => Some(*pos),
+            _ => None
+        }
+    }
+
+    /// The message followed by the offending line of `source` with a caret
+    /// under the error position, or just the message when there is none.
+    pub fn report(&self, source: &str) -> String {
+        match self.pos() {
+            Some(pos) => {
+                let (line, column) = line_column(source, pos);
+                format!("{} (line {}, column {})\n{}", self, line, column, source_context(source, pos, DEFAULT_TAB_WIDTH))
+            },
+            None => self.to_string()
+        }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TypeMismatch { expected, found } => write!(f, "Expected a value of type {:?}, got {:?}", expected, found),
+            Self::AlreadyDeclared(name) => write!(f, "Variable {} was already declared", name),
+            Self::StepLimitExceeded(limit) => write!(f, "Step limit of {} exceeded", limit),
+            Self::OutputLimitExceeded(limit) => write!(f, "Output limit of {} bytes exceeded", limit),
+            Self::StringTooLong { length, max } => write!(f, "String of {} bytes exceeds the maximum length of {} bytes", length, max),
+            Self::Thrown(value) => write!(f, "{}", value),
+            Self::Syntax { message, .. } => write!(f, "{}", message),
+            Self::Message(message) => write!(f, "{}", message)
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// Tab width used by `source_context` unless told otherwise.
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+/// The character index of every line start after the first in `chars`.
+/// `\r\n`, `\n` and a lone `\r` each end one line, so a file gives the same
+/// lines whichever line endings it was saved with.
+fn line_starts(chars: &[char]) -> impl Iterator<Item = usize> + '_ {
+    chars.iter().enumerate()
+        .filter(|(index, char)| **char == '\n' || (**char == '\r' && chars.get(index + 1) != Some(&'\n')))
+        .map(|(index, _)| index + 1)
+}
+
+/// The 1-based line and column of the character at `pos` in `source`.
+pub fn line_column(source: &str, pos: usize) -> (usize, usize) {
+    let chars: Vec<char> = source.chars().collect();
+    let (mut line, mut line_start) = (1, 0);
+    for start in line_starts(&chars).take_while(|start| *start <= pos) {
+        line += 1;
+        line_start = start;
+    }
+    (line, pos - line_start + 1)
+}
+
+/// Renders the line of `source` holding the character at `pos` with a caret
+/// under it. Tabs are expanded to the next multiple of `tab_width` on both
+/// lines so the caret stays aligned however the line is indented.
+pub fn source_context(source: &str, pos: usize, tab_width: usize) -> String {
+    let chars: Vec<char> = source.chars().collect();
+    let line_start = line_starts(&chars).take_while(|start| *start <= pos).last().unwrap_or(0);
+    let line: Vec<char> = chars[line_start..].iter().copied().take_while(|char| *char != '\n' && *char != '\r').collect();
+    let mut rendered = String::new();
+    let mut caret_column = None;
+    for (index, char) in line.iter().enumerate() {
+        if line_start + index == pos {
+            caret_column = Some(rendered.chars().count());
+        }
+        match char {
+            '\t' => {
+                let width = tab_width - rendered.chars().count() % tab_width.max(1);
+                rendered.push_str(&" ".repeat(width));
+            },
+            char => rendered.push(*char)
+        }
+    }
+    let caret_column = caret_column.unwrap_or(rendered.chars().count());
+    format!("{}\n{}^", rendered, " ".repeat(caret_column))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_source_context() {
+        assert_eq!(source_context("let x = 1;\nlet y = @;", 19, DEFAULT_TAB_WIDTH), "let y = @;\n        ^");
+        assert_eq!(source_context("\tlet y = @;", 9, DEFAULT_TAB_WIDTH), "    let y = @;\n            ^");
+        assert_eq!(source_context("if (x) {\n\t\ta\tb;\n}", 13, DEFAULT_TAB_WIDTH), "        a   b;\n            ^");
+        assert_eq!(source_context("\tx", 1, 2), "  x\n  ^");
+        assert_eq!(source_context("abc", 3, DEFAULT_TAB_WIDTH), "abc\n   ^");
+        assert_eq!(source_context("a\r\nb @", 5, DEFAULT_TAB_WIDTH), "b @\n  ^");
+        assert_eq!(source_context("a\rb @\rc", 4, DEFAULT_TAB_WIDTH), "b @\n  ^");
+    }
+
+    #[test]
+    fn test_report() {
+        let syntax = RuntimeError::Syntax { message: "Unexpected token '@' at position 19".to_string(), pos: 19 };
+        assert_eq!(syntax.report("let x = 1;\nlet y = @;"), "Unexpected token '@' at position 19 (line 2, column 9)\nlet y = @;\n        ^");
+        assert_eq!(RuntimeError::Message("boom".to_string()).report("x;"), "boom");
+    }
+
+    #[test]
+    fn test_line_column() {
+        assert_eq!(line_column("abc", 0), (1, 1));
+        assert_eq!(line_column("abc", 2), (1, 3));
+        assert_eq!(line_column("a\nbc", 3), (2, 2));
+        assert_eq!(line_column("a\r\nbc", 4), (2, 2));
+        assert_eq!(line_column("a\rbc", 3), (2, 2));
+        assert_eq!(line_column("a\n\n\r\n\r\nb", 7), (5, 1));
+        assert_eq!(line_column("é\ní", 3), (2, 2));
+    }
+}
@@ -0,0 +1,82 @@
+//! Standard base64 (RFC 4648) with padding, backing the `base64Encode` and
+//! `base64Decode` natives. Implemented here rather than with the `base64`
+//! crate because the build has no access to crates beyond the ones already
+//! vendored; the `base64` feature only gates the natives. Decoding is strict
+//! like the crate's: non-canonical input is rejected.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Returns `None` when `text` is not padded base64 of the standard alphabet.
+pub fn decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut bytes = vec![];
+    for (index, chunk) in text.chunks(4).enumerate() {
+        let is_last = index == text.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|&&byte| byte == b'=').count();
+        if padding > 2 || (padding > 0 && !is_last) {
+            return None;
+        }
+        let mut group: u32 = 0;
+        for &byte in &chunk[..4 - padding] {
+            let value = ALPHABET.iter().position(|&symbol| symbol == byte)?;
+            group = group << 6 | value as u32;
+        }
+        // the bits past the last whole byte must be zero in canonical input
+        if group & ((1 << (2 * padding)) - 1) != 0 {
+            return None;
+        }
+        group <<= 6 * padding;
+        let decoded = [(group >> 16) as u8, (group >> 8) as u8, group as u8];
+        bytes.extend_from_slice(&decoded[..3 - padding]);
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"f"), "Zg==");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(b"foo"), "Zm9v");
+        assert_eq!(encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("Zm9vYg==").as_deref(), Some(&b"foob"[..]));
+        assert_eq!(decode("Zm9vYmE=").as_deref(), Some(&b"fooba"[..]));
+        assert_eq!(decode(""), Some(vec![]));
+        assert_eq!(decode("Zm9"), None);
+        assert_eq!(decode("Zm=v"), None);
+        assert_eq!(decode("Zg==Zg=="), None);
+        assert_eq!(decode("Zm9*"), None);
+        assert_eq!(decode("Zg=="), Some(b"f".to_vec()));
+        assert_eq!(decode("Zh=="), None);
+        assert_eq!(decode("Zm8="), Some(b"fo".to_vec()));
+        assert_eq!(decode("Zm9="), None);
+    }
+}
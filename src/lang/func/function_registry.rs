@@ -1,28 +1,63 @@
-use std::{collections::HashMap};
+use std::{collections::HashMap, rc::Rc};
 
 use crate::lang::interpreter::RuntimeValue;
 
-use super::native_functions::load_native_functions;
+use super::{native_functions::load_native_functions, native_state::NativeState};
 
 pub struct FunctionRegistry {
-    functions: HashMap<String, Function>
+    functions: HashMap<String, Function>,
+    state: Rc<NativeState>
+}
+
+impl Default for FunctionRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FunctionRegistry {
     pub fn new() -> Self {
         let mut instance = Self {
-            functions: HashMap::new()
+            functions: HashMap::new(),
+            state: Rc::new(NativeState::new())
         };
         load_native_functions(&mut instance);
         instance
     }
 
+    pub fn state(&self) -> &Rc<NativeState> {
+        &self.state
+    }
+
     pub fn add_function(&mut self, function: Function) {
         self.functions.insert(function.name.to_owned(), function);
     }
 
+    /// Registers `new_name` as another name for the function `existing`, sharing
+    /// its implementation. Returns false when `existing` is not registered.
+    pub fn alias(&mut self, existing: &str, new_name: &str) -> bool {
+        let Some(function) = self.functions.get(existing) else {
+            return false;
+        };
+        let aliased = Function { name: new_name.to_string(), ..function.clone() };
+        self.add_function(aliased);
+        true
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.functions.keys()
+    }
+
+    pub fn signature(&self, function_name: &str) -> Option<Signature> {
+        self.functions.get(function_name).map(|function| Signature {
+            param_count: function.expected_params.clone(),
+            param_types: function.param_types.clone(),
+            return_type: function.return_type.clone()
+        })
+    }
+
     pub fn call(&self, function_name: &str, args: Vec<RuntimeValue>) -> RuntimeValue {
-        let function = self.functions.get(function_name).expect(&format!("Function {} does not exist", function_name));
+        let function = self.functions.get(function_name).unwrap_or_else(|| panic!("Function {} does not exist", function_name));
         match function.expected_params {
             ParamCount::Fixed(num) => {
                 if num != args.len() {
@@ -36,9 +71,8 @@ impl FunctionRegistry {
             },
         }
 
-        let mut index: usize = 0;
         for (i, arg) in args.iter().enumerate() {
-            index = i;
+            let mut index = i;
             if !function.expected_params.is_fixed() && i >= function.param_types.len() {
                 index = function.param_types.len() - 1;
             }
@@ -50,7 +84,7 @@ impl FunctionRegistry {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParamCount {
     Fixed(usize),
     Dynamic(usize)
@@ -62,64 +96,156 @@ impl ParamCount {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum RuntimeType {
     Number,
     String,
     Bool,
+    Array,
+    Object,
+    Function,
     Any,
     Null,
 }
 
 impl RuntimeValue {
     fn matches_type(&self, runtime_type: &RuntimeType) -> bool {
-        match (self.to_type(), runtime_type) {
-            (RuntimeType::Number, RuntimeType::Number) => true,
-            (RuntimeType::Bool, RuntimeType::Bool) => true,
-            (RuntimeType::String, RuntimeType::String) => true,
-            (RuntimeType::Null, RuntimeType::Null) => true,
-            (_, RuntimeType::Any) => true,
-            _ => false
-        }
+        matches!(
+            (self.to_type(), runtime_type),
+            (RuntimeType::Number, RuntimeType::Number) |
+            (RuntimeType::Bool, RuntimeType::Bool) |
+            (RuntimeType::String, RuntimeType::String) |
+            (RuntimeType::Array, RuntimeType::Array) |
+            (RuntimeType::Object, RuntimeType::Object) |
+            (RuntimeType::Function, RuntimeType::Function) |
+            (RuntimeType::Null, RuntimeType::Null) |
+            (_, RuntimeType::Any)
+        )
     }
 }
 
+#[derive(Clone)]
 pub struct Function {
     name: String,
     expected_params: ParamCount,
     param_types: Vec<RuntimeType>,
-    implementation: Box<dyn Fn(Arguments) -> RuntimeValue>
+    return_type: RuntimeType,
+    implementation: Rc<dyn Fn(Arguments) -> RuntimeValue>
 }
 
 impl Function {
-    pub fn new(name: String, expected_params: ParamCount, param_types: Vec<RuntimeType>, implementation: Box<dyn Fn(Arguments) -> RuntimeValue>) -> Self {
-        Self { name, expected_params, param_types, implementation }
+    pub fn new(name: String, expected_params: ParamCount, param_types: Vec<RuntimeType>, return_type: RuntimeType, implementation: Box<dyn Fn(Arguments) -> RuntimeValue>) -> Self {
+        Self { name, expected_params, param_types, return_type, implementation: Rc::from(implementation) }
+    }
+
+    pub fn builder(name: &str) -> FunctionBuilder {
+        FunctionBuilder {
+            name: name.to_string(),
+            params: vec![],
+            optional: vec![],
+            variadic: None,
+            return_type: RuntimeType::Any
+        }
+    }
+}
+
+/// Builds a `Function` declaring its params in order: required params first,
+/// then optional ones and finally an optional variadic tail.
+pub struct FunctionBuilder {
+    name: String,
+    params: Vec<RuntimeType>,
+    optional: Vec<RuntimeType>,
+    variadic: Option<RuntimeType>,
+    return_type: RuntimeType
+}
+
+impl FunctionBuilder {
+    pub fn param(mut self, param_type: RuntimeType) -> Self {
+        self.params.push(param_type);
+        self
+    }
+
+    /// A param that may be left out. Calls with more arguments than declared
+    /// params are rejected unless the function is also variadic.
+    pub fn optional(mut self, param_type: RuntimeType) -> Self {
+        self.optional.push(param_type);
+        self
+    }
+
+    /// Accepts any number of trailing arguments of `param_type`.
+    pub fn variadic(mut self, param_type: RuntimeType) -> Self {
+        self.variadic = Some(param_type);
+        self
+    }
+
+    /// Defaults to `RuntimeType::Any` when not set.
+    pub fn returns(mut self, return_type: RuntimeType) -> Self {
+        self.return_type = return_type;
+        self
+    }
+
+    pub fn build(self, implementation: impl Fn(Arguments) -> RuntimeValue + 'static) -> Function {
+        let required = self.params.len();
+        let max = required + self.optional.len();
+        let expected_params = match (self.optional.is_empty(), &self.variadic) {
+            (true, None) => ParamCount::Fixed(required),
+            _ => ParamCount::Dynamic(required)
+        };
+        let mut param_types = self.params;
+        param_types.extend(self.optional);
+        let implementation: Box<dyn Fn(Arguments) -> RuntimeValue> = match self.variadic {
+            Some(variadic) => {
+                param_types.push(variadic);
+                Box::new(implementation)
+            },
+            None if max > required => {
+                let name = self.name.clone();
+                Box::new(move |args: Arguments| {
+                    if args.len() > max {
+                        panic!("Function {} expects {} params as maximum, got {}", name, max, args.len());
+                    }
+                    implementation(args)
+                })
+            },
+            None => Box::new(implementation)
+        };
+        Function::new(self.name, expected_params, param_types, self.return_type, implementation)
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub param_count: ParamCount,
+    pub param_types: Vec<RuntimeType>,
+    pub return_type: RuntimeType
+}
+
 
 pub struct Arguments {
-    args: Vec<RuntimeValue>,
-    index: usize
+    args: Vec<RuntimeValue>
 }
 
 impl Arguments {
 
     pub fn new(args: Vec<RuntimeValue>) -> Self {
-        Arguments { args, index: 0 }
+        Arguments { args }
     }
 
     pub fn len(&self) -> usize {
         self.args.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
     pub fn args(&self) -> &Vec<RuntimeValue> {
         &self.args
     }
 
-    fn get<T>(&self, index: usize, extractor: fn(&RuntimeValue) -> Option<&T>, expected: &str) -> &T {
-        let runtime_value = self.args.get(index).expect(&format!("Missing argument at position {}", index));
-        extractor(&runtime_value).unwrap_or_else(|| {
+    fn get<T: ?Sized>(&self, index: usize, extractor: fn(&RuntimeValue) -> Option<&T>, expected: &str) -> &T {
+        let runtime_value = self.args.get(index).unwrap_or_else(|| panic!("Missing argument at position {}", index));
+        extractor(runtime_value).unwrap_or_else(|| {
             panic!("Expected argument at position {} to be a {} but got something else", index, expected);
         })
     }
@@ -128,8 +254,8 @@ impl Arguments {
         index < self.args.len()
     }
 
-    pub fn as_str(&self, index: usize) -> &String {
-        self.get(index, |rv| {
+    pub fn as_str(&self, index: usize) -> &str {
+        self.get::<str>(index, |rv| {
             match rv {
                 RuntimeValue::String(value) => Some(value),
                 _ => None
@@ -154,12 +280,17 @@ impl Arguments {
         }, "Number")
     }
 
-    pub fn as_any(&self, index: usize) -> &RuntimeValue {
-        self.get::<RuntimeValue>(index, |rv| {
+    pub fn as_array(&self, index: usize) -> &Vec<RuntimeValue> {
+        self.get(index, |rv| {
             match rv {
-                value => Some(value)
+                RuntimeValue::Array(value) => Some(value),
+                _ => None
             }
-        }, "Any")
+        }, "Array")
+    }
+
+    pub fn as_any(&self, index: usize) -> &RuntimeValue {
+        self.get::<RuntimeValue>(index, |rv| Some(rv), "Any")
     }
 }
 
@@ -174,7 +305,8 @@ mod test {
             name: "sum".to_string(),
             expected_params: ParamCount::Fixed(2),
             param_types: vec![RuntimeType::Number, RuntimeType::Number],
-            implementation: Box::new(|args: Arguments| -> RuntimeValue {
+            return_type: RuntimeType::Number,
+            implementation: Rc::new(|args: Arguments| -> RuntimeValue {
                 let num1 = args.as_f32(0);
                 let num2 = args.as_f32(1);
                 RuntimeValue::Number(num1 + num2)
@@ -187,24 +319,84 @@ mod test {
             name: "concat".to_string(),
             expected_params: ParamCount::Dynamic(2),
             param_types: vec![RuntimeType::String, RuntimeType::String],
-            implementation: Box::new(|args: Arguments| -> RuntimeValue {
+            return_type: RuntimeType::String,
+            implementation: Rc::new(|args: Arguments| -> RuntimeValue {
                 let mut result = String::new();
                 for i in 0..args.len() {
-                    result.push_str(&args.as_str(i));
+                    result.push_str(args.as_str(i));
                 }
-                RuntimeValue::String(result)
+                RuntimeValue::String(result.into())
             })
         });
 
-        let result2 = registry.call("concat", vec![RuntimeValue::String("hello ".to_owned()), RuntimeValue::String("world".to_owned()), RuntimeValue::String(" simon".to_owned())]);
+        let result2 = registry.call("concat", vec![RuntimeValue::String("hello ".into()), RuntimeValue::String("world".into()), RuntimeValue::String(" simon".into())]);
         
-        assert_eq!(result2, RuntimeValue::String("hello world simon".to_owned()));
+        assert_eq!(result2, RuntimeValue::String("hello world simon".into()));
 
         let result3 = registry.call("print", vec![RuntimeValue::Number(34.)]);
         assert_eq!(result3, RuntimeValue::Null);
 
-        registry.call("print", vec![RuntimeValue::String("Write 'hi!'".to_owned())]);
+        registry.call("print", vec![RuntimeValue::String("Write 'hi!'".into())]);
         let result3 = registry.call("read", vec![]);
-        assert_eq!(result3, RuntimeValue::String("hi!".to_string()));
+        assert_eq!(result3, RuntimeValue::String("hi!".into()));
+    }
+
+    #[test]
+    fn test_signature() {
+        let registry = FunctionRegistry::new();
+        let signature = registry.signature("substring").unwrap();
+        assert_eq!(signature.param_count, ParamCount::Fixed(3));
+        assert_eq!(signature.param_types, vec![RuntimeType::String, RuntimeType::Number, RuntimeType::Number]);
+        assert_eq!(signature.return_type, RuntimeType::String);
+        assert!(registry.signature("unknown").is_none());
+    }
+
+    #[test]
+    fn test_alias() {
+        let mut registry = FunctionRegistry::new();
+        assert!(registry.alias("print", "echo"));
+        assert!(!registry.alias("unknown", "other"));
+        assert_eq!(registry.call("print", vec![RuntimeValue::String("from print".into())]), RuntimeValue::Null);
+        assert_eq!(registry.call("echo", vec![RuntimeValue::String("from echo".into())]), RuntimeValue::Null);
+        assert_eq!(registry.signature("echo"), registry.signature("print"));
+        assert!(registry.signature("other").is_none());
+    }
+
+    #[test]
+    fn test_builder() {
+        let mut registry = FunctionRegistry::new();
+        registry.add_function(Function::builder("join")
+            .param(RuntimeType::String)
+            .variadic(RuntimeType::String)
+            .returns(RuntimeType::String)
+            .build(|args| {
+                let parts: Vec<&str> = (1..args.len()).map(|i| args.as_str(i)).collect();
+                RuntimeValue::String(parts.join(args.as_str(0)).into())
+            })
+        );
+        let strings = |values: &[&str]| values.iter().map(|value| RuntimeValue::String((*value).into())).collect::<Vec<_>>();
+        assert_eq!(registry.call("join", strings(&["-", "a", "b", "c"])), RuntimeValue::String("a-b-c".into()));
+        assert_eq!(registry.call("join", strings(&["-"])), RuntimeValue::String("".into()));
+        assert_eq!(registry.signature("join").unwrap(), Signature {
+            param_count: ParamCount::Dynamic(1),
+            param_types: vec![RuntimeType::String, RuntimeType::String],
+            return_type: RuntimeType::String
+        });
+
+        registry.add_function(Function::builder("inc")
+            .param(RuntimeType::Number)
+            .optional(RuntimeType::Number)
+            .build(|args| {
+                let step = if args.has(1) { *args.as_f32(1) } else { 1. };
+                RuntimeValue::Number(args.as_f32(0) + step)
+            })
+        );
+        assert_eq!(registry.call("inc", vec![RuntimeValue::Number(1.)]), RuntimeValue::Number(2.));
+        assert_eq!(registry.call("inc", vec![RuntimeValue::Number(1.), RuntimeValue::Number(5.)]), RuntimeValue::Number(6.));
+        assert_eq!(registry.signature("inc").unwrap().return_type, RuntimeType::Any);
+        let too_many = crate::lang::panics::catch(|| {
+            registry.call("inc", vec![RuntimeValue::Number(1.), RuntimeValue::Number(2.), RuntimeValue::Number(3.)])
+        });
+        assert_eq!(too_many.unwrap_err().to_string(), "Function inc expects 2 params as maximum, got 3");
     }
-}
\ No newline at end of file
+}
@@ -1,2 +1,7 @@
 pub mod function_registry;
-pub mod native_functions;
\ No newline at end of file
+pub mod native_functions;
+pub mod native_state;
+#[cfg(feature = "base64")]
+mod base64;
+#[cfg(feature = "science")]
+mod science;
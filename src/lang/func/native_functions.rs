@@ -1,89 +1,537 @@
-use std::{fs, io, path::Path};
+use std::{fs, path::{Path, PathBuf}, rc::Rc};
 
-use crate::lang::interpreter::RuntimeValue;
+use crate::lang::{interpreter::RuntimeValue, printer, tokenizer};
 
-use super::function_registry::{Function, FunctionRegistry, ParamCount, RuntimeType};
+#[cfg(feature = "base64")]
+use super::base64;
+#[cfg(feature = "science")]
+use super::science;
+use super::function_registry::{Function, FunctionRegistry, RuntimeType};
+
+/// Most elements `fill` and `range` may build, so a script cannot make the
+/// host allocate gigabytes with a single call.
+pub const MAX_ARRAY_LEN: usize = 1 << 22;
 
 pub fn load_native_functions(fr: &mut FunctionRegistry) {
-    fr.add_function(
-        Function::new(
-            "print".to_string(),
-            ParamCount::Fixed(1),
-            vec![RuntimeType::Any],
-            Box::new(|args| {
-                let value = args.as_any(0);
-                match value {
-                    RuntimeValue::Null => println!("null"),
-                    RuntimeValue::Bool(value) => println!("{}", value),
-                    RuntimeValue::Number(value) => println!("{}", value),
-                    RuntimeValue::String(value) => println!("{}", value)
-                }
-                RuntimeValue::Null
-            })
-        )
-    );
-    fr.add_function(
-        Function::new(
-            "read".to_string(),
-            ParamCount::Fixed(0),
-            vec![],
-            Box::new(|_| {
-                let mut value = String::new();
-                io::stdin().read_line(&mut value).expect("Error when reading from console");
-                if value.ends_with("\n") {
-                    value.pop();
-                }
-                RuntimeValue::String(value)
-            })
-        )
-    );
-    fr.add_function(Function::new(
-        "random".to_string(),
-        ParamCount::Fixed(0),
-        vec![],
-        Box::new(|_| {
-            let random_number = rand::random::<f32>();
-            RuntimeValue::Number(random_number)
-        })
-    ));
-    fr.add_function(Function::new(
-        "toNumber".to_string(),
-        ParamCount::Fixed(1),
-        vec![RuntimeType::String],
-        Box::new(|args| {
+    let state = Rc::clone(fr.state());
+    fr.add_function(Function::builder("print")
+        .param(RuntimeType::Any)
+        .returns(RuntimeType::Null)
+        .build(move |args| {
+            let value = args.as_any(0);
+            state.write_line(&state.display(value));
+            RuntimeValue::Null
+        })
+    );
+    let state = Rc::clone(fr.state());
+    fr.add_function(Function::builder("printJson")
+        .param(RuntimeType::Any)
+        .returns(RuntimeType::Null)
+        .build(move |args| {
+            state.write_line(&args.as_any(0).to_json());
+            RuntimeValue::Null
+        })
+    );
+    let state = Rc::clone(fr.state());
+    fr.add_function(Function::builder("printErr")
+        .param(RuntimeType::Any)
+        .returns(RuntimeType::Null)
+        .build(move |args| {
+            let value = args.as_any(0);
+            state.write_error_line(&state.display(value));
+            RuntimeValue::Null
+        })
+    );
+    fr.alias("printErr", "eprint");
+    let state = Rc::clone(fr.state());
+    fr.add_function(Function::builder("read")
+        .returns(RuntimeType::String)
+        .build(move |_| RuntimeValue::String(state.read_line().into()))
+    );
+    // invalid input gives null, or an error when `strict` is true
+    let state = Rc::clone(fr.state());
+    fr.add_function(Function::builder("readNumber")
+        .optional(RuntimeType::Bool)
+        .build(move |args| {
+            let line = state.read_line();
+            match line.trim().parse::<f32>() {
+                Ok(n) => RuntimeValue::Number(n),
+                Err(_) if args.has(0) && *args.as_bool(0) => panic!("Function readNumber expected a number, got {:?}", line),
+                Err(_) => RuntimeValue::Null
+            }
+        })
+    );
+    let state = Rc::clone(fr.state());
+    fr.add_function(Function::builder("scriptPath")
+        .build(move |_| path_value(state.script_path()))
+    );
+    let state = Rc::clone(fr.state());
+    fr.add_function(Function::builder("scriptDir")
+        .build(move |_| path_value(state.script_dir()))
+    );
+    let state = Rc::clone(fr.state());
+    fr.add_function(Function::builder("random")
+        .returns(RuntimeType::Number)
+        .build(move |_| RuntimeValue::Number(state.random()))
+    );
+    fr.add_function(Function::builder("toNumber")
+        .param(RuntimeType::String)
+        .build(|args| {
             let str_value = args.as_str(0);
             match str_value.parse::<f32>() {
                 Ok(n) => RuntimeValue::Number(n),
                 Err(_) => RuntimeValue::Null
             }
         })
-    ));
-    fr.add_function(Function::new(
-        "toString".to_string(),
-        ParamCount::Fixed(1),
-        vec![RuntimeType::Number],
-        Box::new(|args| {
-            let num_value = args.as_f32(0);
-            RuntimeValue::String(num_value.to_string())
-        })
-    ));
-    fr.add_function(Function::new(
-        "substring".to_string(),
-        ParamCount::Fixed(3),
-        vec![RuntimeType::String, RuntimeType::Number, RuntimeType::Number],
-        Box::new(|args| {
+    );
+    fr.add_function(Function::builder("toInt")
+        .param(RuntimeType::Any)
+        .returns(RuntimeType::Number)
+        .build(|args| {
+            let number = match args.as_any(0) {
+                RuntimeValue::Number(value) => *value,
+                RuntimeValue::String(value) => match value.trim().parse::<f32>() {
+                    Ok(value) => value,
+                    Err(_) => panic!("Cannot convert {:?} to an integer", value)
+                },
+                value => panic!("Function toInt expects a Number or a String, got {:?}", value.to_type())
+            };
+            if !number.is_finite() {
+                panic!("Cannot convert {} to an integer", number);
+            }
+            RuntimeValue::Number(number.trunc())
+        })
+    );
+    fr.add_function(Function::builder("isFinite")
+        .param(RuntimeType::Number)
+        .returns(RuntimeType::Bool)
+        .build(|args| RuntimeValue::Bool(args.as_f32(0).is_finite()))
+    );
+    fr.add_function(Function::builder("toBool")
+        .param(RuntimeType::Any)
+        .returns(RuntimeType::Bool)
+        .build(|args| RuntimeValue::Bool(args.as_any(0).is_truthy()))
+    );
+    let state = Rc::clone(fr.state());
+    fr.add_function(Function::builder("toString")
+        .param(RuntimeType::Number)
+        .returns(RuntimeType::String)
+        .build(move |args| RuntimeValue::String(state.display(args.as_any(0)).into()))
+    );
+    for (name, function) in [("abs", f32::abs as fn(f32) -> f32), ("floor", f32::floor), ("ceil", f32::ceil), ("round", f32::round)] {
+        fr.add_function(Function::builder(name)
+            .param(RuntimeType::Number)
+            .returns(RuntimeType::Number)
+            .build(move |args| RuntimeValue::Number(function(*args.as_f32(0))))
+        );
+    }
+    fr.add_function(Function::builder("sqrt")
+        .param(RuntimeType::Number)
+        .returns(RuntimeType::Number)
+        .build(|args| {
+            let value = *args.as_f32(0);
+            if value < 0. {
+                panic!("Cannot take the square root of a negative number: sqrt({})", value);
+            }
+            RuntimeValue::Number(value.sqrt())
+        })
+    );
+    fr.add_function(Function::builder("max")
+        .param(RuntimeType::Number)
+        .variadic(RuntimeType::Number)
+        .returns(RuntimeType::Number)
+        .build(|args| {
+            let max = (0..args.len()).map(|i| *args.as_f32(i)).fold(f32::NEG_INFINITY, f32::max);
+            RuntimeValue::Number(max)
+        })
+    );
+    fr.add_function(Function::builder("min")
+        .param(RuntimeType::Number)
+        .variadic(RuntimeType::Number)
+        .returns(RuntimeType::Number)
+        .build(|args| {
+            let min = (0..args.len()).map(|i| *args.as_f32(i)).fold(f32::INFINITY, f32::min);
+            RuntimeValue::Number(min)
+        })
+    );
+    fr.add_function(Function::builder("floorDiv")
+        .param(RuntimeType::Number)
+        .param(RuntimeType::Number)
+        .returns(RuntimeType::Number)
+        .build(|args| {
+            let (left, right) = (*args.as_f32(0), *args.as_f32(1));
+            if right == 0. {
+                panic!("Cannot divide by zero: floorDiv({}, {})", left, right);
+            }
+            RuntimeValue::Number((left / right).floor())
+        })
+    );
+    // floored modulo: unlike `%`, the result takes the sign of the divisor
+    fr.add_function(Function::builder("mod")
+        .param(RuntimeType::Number)
+        .param(RuntimeType::Number)
+        .returns(RuntimeType::Number)
+        .build(|args| {
+            let (left, right) = (*args.as_f32(0), *args.as_f32(1));
+            if right == 0. {
+                panic!("Cannot divide by zero: mod({}, {})", left, right);
+            }
+            RuntimeValue::Number(left - right * (left / right).floor())
+        })
+    );
+    fr.add_function(Function::builder("formatNumber")
+        .param(RuntimeType::Number)
+        .optional(RuntimeType::String)
+        .returns(RuntimeType::String)
+        .build(|args| {
+            let separator = if args.has(1) { args.as_str(1) } else { "," };
+            RuntimeValue::String(group_thousands(*args.as_f32(0), separator).into())
+        })
+    );
+    fr.add_function(Function::builder("substring")
+        .param(RuntimeType::String)
+        .param(RuntimeType::Number)
+        .param(RuntimeType::Number)
+        .returns(RuntimeType::String)
+        .build(|args| {
             let str_value = args.as_str(0);
             let idx_start = *args.as_f32(1) as usize;
             let idx_end = *args.as_f32(2) as usize;
             let sub_str = &str_value[idx_start..=idx_end];
-            RuntimeValue::String(sub_str.to_string())
-        })
-    ));
-    fr.add_function(Function::new(
-        "writeFile".to_string(),
-        ParamCount::Fixed(2),
-        vec![RuntimeType::String, RuntimeType::String],
-        Box::new(|args| {
+            RuntimeValue::String(sub_str.into())
+        })
+    );
+    fr.add_function(Function::builder("trim")
+        .param(RuntimeType::String)
+        .returns(RuntimeType::String)
+        .build(|args| {
+            let str_value = args.as_str(0);
+            RuntimeValue::String(str_value.trim().into())
+        })
+    );
+    fr.add_function(Function::builder("toLower")
+        .param(RuntimeType::String)
+        .returns(RuntimeType::String)
+        .build(|args| {
+            let str_value = args.as_str(0);
+            RuntimeValue::String(str_value.to_lowercase().into())
+        })
+    );
+    fr.add_function(Function::builder("toUpper")
+        .param(RuntimeType::String)
+        .returns(RuntimeType::String)
+        .build(|args| {
+            let str_value = args.as_str(0);
+            RuntimeValue::String(str_value.to_uppercase().into())
+        })
+    );
+    fr.alias("toLower", "lowercase");
+    fr.alias("toUpper", "uppercase");
+    // replaces every occurrence, not only the first
+    fr.add_function(Function::builder("replace")
+        .param(RuntimeType::String)
+        .param(RuntimeType::String)
+        .param(RuntimeType::String)
+        .returns(RuntimeType::String)
+        .build(|args| {
+            let (value, from, to) = (args.as_str(0), args.as_str(1), args.as_str(2));
+            if from.is_empty() {
+                panic!("Function replace expects a non-empty string to replace");
+            }
+            RuntimeValue::String(value.replace(from, to).into())
+        })
+    );
+    fr.add_function(Function::builder("contains")
+        .param(RuntimeType::String)
+        .param(RuntimeType::String)
+        .returns(RuntimeType::Bool)
+        .build(|args| RuntimeValue::Bool(args.as_str(0).contains(args.as_str(1))))
+    );
+    // an empty separator splits between every character
+    fr.add_function(Function::builder("split")
+        .param(RuntimeType::String)
+        .param(RuntimeType::String)
+        .returns(RuntimeType::Array)
+        .build(|args| {
+            let (value, separator) = (args.as_str(0), args.as_str(1));
+            let parts: Vec<RuntimeValue> = if separator.is_empty() {
+                value.chars().map(|char| RuntimeValue::String(char.to_string().into())).collect()
+            } else {
+                value.split(separator).map(|part| RuntimeValue::String(part.into())).collect()
+            };
+            RuntimeValue::Array(parts)
+        })
+    );
+    // elements that are not strings are joined in the form `toString` gives them
+    let state = Rc::clone(fr.state());
+    fr.add_function(Function::builder("join")
+        .param(RuntimeType::Array)
+        .param(RuntimeType::String)
+        .returns(RuntimeType::String)
+        .build(move |args| {
+            let parts: Vec<String> = args.as_array(0).iter().map(|value| match value {
+                RuntimeValue::String(value) => value.to_string(),
+                value => state.display(value)
+            }).collect();
+            RuntimeValue::String(parts.join(args.as_str(1)).into())
+        })
+    );
+    fr.add_function(Function::builder("hash")
+        .param(RuntimeType::String)
+        .returns(RuntimeType::Number)
+        .build(|args| RuntimeValue::Number(fnv_hash(args.as_str(0)) as f32))
+    );
+    #[cfg(feature = "base64")]
+    fr.add_function(Function::builder("base64Encode")
+        .param(RuntimeType::String)
+        .returns(RuntimeType::String)
+        .build(|args| RuntimeValue::String(base64::encode(args.as_str(0).as_bytes()).into()))
+    );
+    #[cfg(feature = "base64")]
+    fr.add_function(Function::builder("base64Decode")
+        .param(RuntimeType::String)
+        .build(|args| {
+            match base64::decode(args.as_str(0)).and_then(|bytes| String::from_utf8(bytes).ok()) {
+                Some(decoded) => RuntimeValue::String(decoded.into()),
+                None => RuntimeValue::Null
+            }
+        })
+    );
+    #[cfg(feature = "science")]
+    load_science_functions(fr);
+    fr.add_function(Function::builder("escape")
+        .param(RuntimeType::String)
+        .returns(RuntimeType::String)
+        .build(|args| RuntimeValue::String(printer::escape(args.as_str(0)).into()))
+    );
+    fr.add_function(Function::builder("unescape")
+        .param(RuntimeType::String)
+        .returns(RuntimeType::String)
+        .build(|args| RuntimeValue::String(tokenizer::unescape(args.as_str(0)).into()))
+    );
+    fr.add_function(Function::builder("urlEncode")
+        .param(RuntimeType::String)
+        .returns(RuntimeType::String)
+        .build(|args| RuntimeValue::String(percent_encode(args.as_str(0)).into()))
+    );
+    fr.add_function(Function::builder("urlDecode")
+        .param(RuntimeType::String)
+        .build(|args| {
+            match percent_decode(args.as_str(0)) {
+                Some(decoded) => RuntimeValue::String(decoded.into()),
+                None => RuntimeValue::Null
+            }
+        })
+    );
+    fr.add_function(Function::builder("parseCsv")
+        .param(RuntimeType::String)
+        .returns(RuntimeType::Array)
+        .build(|args| {
+            let rows = parse_csv(args.as_str(0)).into_iter()
+                .map(|row| RuntimeValue::Array(row.into_iter().map(|field| RuntimeValue::String(field.into())).collect()))
+                .collect();
+            RuntimeValue::Array(rows)
+        })
+    );
+    fr.add_function(Function::builder("keys")
+        .param(RuntimeType::Object)
+        .returns(RuntimeType::Array)
+        .build(|args| match args.as_any(0) {
+            RuntimeValue::Object(entries) => RuntimeValue::Array(entries.keys().map(|key| RuntimeValue::String(key.as_str().into())).collect()),
+            _ => unreachable!("keys is only called with an Object")
+        })
+    );
+    // the end is excluded; a negative step counts down from start to end
+    fr.add_function(Function::builder("range")
+        .param(RuntimeType::Number)
+        .param(RuntimeType::Number)
+        .optional(RuntimeType::Number)
+        .returns(RuntimeType::Array)
+        .build(|args| {
+            let (start, end) = (*args.as_f32(0), *args.as_f32(1));
+            let step = if args.len() > 2 { *args.as_f32(2) } else { 1. };
+            if !start.is_finite() || !end.is_finite() || !step.is_finite() {
+                panic!("Function range expects finite numbers, got range({}, {}, {})", start, end, step);
+            }
+            if step == 0. {
+                panic!("Function range expects a non-zero step, got {}", step);
+            }
+            if (step > 0. && start > end) || (step < 0. && start < end) {
+                panic!("Function range cannot reach {} from {} with a step of {}", end, start, step);
+            }
+            let count = array_len("range", ((end - start) / step).ceil().max(0.));
+            RuntimeValue::Array((0..count).map(|i| RuntimeValue::Number(start + step * i as f32)).collect())
+        })
+    );
+    fr.add_function(Function::builder("typeof")
+        .param(RuntimeType::Any)
+        .returns(RuntimeType::String)
+        .build(|args| RuntimeValue::String(args.as_any(0).type_name().into()))
+    );
+    fr.add_function(Function::builder("typesOf")
+        .param(RuntimeType::Array)
+        .returns(RuntimeType::Array)
+        .build(|args| {
+            let types = args.as_array(0).iter().map(|value| RuntimeValue::String(value.type_name().into())).collect();
+            RuntimeValue::Array(types)
+        })
+    );
+    fr.add_function(Function::builder("len")
+        .param(RuntimeType::Any)
+        .returns(RuntimeType::Number)
+        .build(|args| {
+            match args.as_any(0) {
+                RuntimeValue::Array(values) => RuntimeValue::Number(values.len() as f32),
+                RuntimeValue::String(value) => RuntimeValue::Number(value.chars().count() as f32),
+                value => panic!("Function len expects an Array or a String, got {:?}", value.to_type())
+            }
+        })
+    );
+    fr.add_function(Function::builder("byteLength")
+        .param(RuntimeType::String)
+        .returns(RuntimeType::Number)
+        .build(|args| RuntimeValue::Number(args.as_str(0).len() as f32))
+    );
+    fr.add_function(Function::builder("at")
+        .param(RuntimeType::Any)
+        .param(RuntimeType::Number)
+        .build(|args| {
+            let index = *args.as_f32(1);
+            if index.fract() != 0. || !index.is_finite() {
+                panic!("Index must be a whole number, got {}", index);
+            }
+            let index = index as isize;
+            match args.as_any(0) {
+                RuntimeValue::Array(values) => wrap_index(index, values.len())
+                    .map(|i| values[i].clone())
+                    .unwrap_or(RuntimeValue::Null),
+                RuntimeValue::String(value) => {
+                    let chars: Vec<char> = value.chars().collect();
+                    wrap_index(index, chars.len())
+                        .map(|i| RuntimeValue::String(chars[i].to_string().into()))
+                        .unwrap_or(RuntimeValue::Null)
+                },
+                value => panic!("Function at expects an Array or a String, got {:?}", value.to_type())
+            }
+        })
+    );
+    fr.add_function(Function::builder("slice")
+        .param(RuntimeType::Any)
+        .param(RuntimeType::Number)
+        .param(RuntimeType::Number)
+        .optional(RuntimeType::Number)
+        .build(|args| {
+            let start = *args.as_f32(1) as isize;
+            let end = *args.as_f32(2) as isize;
+            let step = if args.has(3) { *args.as_f32(3) as isize } else { 1 };
+            if step == 0 {
+                panic!("Function slice expects a non-zero step");
+            }
+            match args.as_any(0) {
+                RuntimeValue::String(value) => {
+                    let chars: Vec<char> = value.chars().collect();
+                    let indexes = slice_indexes(chars.len(), start, end, step);
+                    RuntimeValue::String(indexes.into_iter().map(|i| chars[i]).collect::<String>().into())
+                },
+                RuntimeValue::Array(values) => {
+                    let indexes = slice_indexes(values.len(), start, end, step);
+                    RuntimeValue::Array(indexes.into_iter().map(|i| values[i].clone()).collect())
+                },
+                value => panic!("Function slice expects an Array or a String, got {:?}", value.to_type())
+            }
+        })
+    );
+    fr.add_function(Function::builder("fill")
+        .param(RuntimeType::Any)
+        .param(RuntimeType::Number)
+        .returns(RuntimeType::Array)
+        .build(|args| {
+            let count = *args.as_f32(1);
+            if count < 0. || count.fract() != 0. || !count.is_finite() {
+                panic!("Function fill expects a non-negative whole count, got {}", count);
+            }
+            RuntimeValue::Array(vec![args.as_any(0).clone(); array_len("fill", count)])
+        })
+    );
+    fr.add_function(Function::builder("array")
+        .variadic(RuntimeType::Any)
+        .returns(RuntimeType::Array)
+        .build(|args| RuntimeValue::Array(args.args().clone()))
+    );
+    // Values are copied into variables, so neither function changes the array
+    // it is given: keep the result of `push` with `a = push(a, x)` and drop the
+    // last element with `slice(a, 0, -1)`.
+    fr.add_function(Function::builder("push")
+        .param(RuntimeType::Array)
+        .param(RuntimeType::Any)
+        .returns(RuntimeType::Array)
+        .build(|args| {
+            let mut values = args.as_array(0).clone();
+            values.push(args.as_any(1).clone());
+            RuntimeValue::Array(values)
+        })
+    );
+    fr.add_function(Function::builder("pop")
+        .param(RuntimeType::Array)
+        .build(|args| args.as_array(0).last().cloned().unwrap_or(RuntimeValue::Null))
+    );
+    // `Clone` on a value is already a deep copy: arrays and objects own their
+    // elements in a `Vec` and a map, while strings are a shared `Rc<str>` that
+    // nothing changes in place. `let b = a` copies the same way, so clone only
+    // states the intent. If arrays or objects ever become `Rc`-backed this has
+    // to copy them element by element instead.
+    fr.add_function(Function::builder("clone")
+        .param(RuntimeType::Any)
+        .build(|args| args.as_any(0).clone())
+    );
+    fr.add_function(Function::builder("chunk")
+        .param(RuntimeType::Array)
+        .param(RuntimeType::Number)
+        .returns(RuntimeType::Array)
+        .build(|args| {
+            let values = args.as_array(0);
+            let size = *args.as_f32(1);
+            if size < 1. || size.fract() != 0. || !size.is_finite() {
+                panic!("Function chunk expects a whole size of at least 1, got {}", size);
+            }
+            let chunks = values.chunks(size as usize)
+                .map(|chunk| RuntimeValue::Array(chunk.to_vec()))
+                .collect();
+            RuntimeValue::Array(chunks)
+        })
+    );
+    fr.add_function(Function::builder("zip")
+        .param(RuntimeType::Array)
+        .param(RuntimeType::Array)
+        .variadic(RuntimeType::Array)
+        .returns(RuntimeType::Array)
+        .build(|args| {
+            let arrays: Vec<&Vec<RuntimeValue>> = (0..args.len()).map(|i| args.as_array(i)).collect();
+            let shortest = arrays.iter().map(|array| array.len()).min().unwrap_or(0);
+            let tuples = (0..shortest)
+                .map(|i| RuntimeValue::Array(arrays.iter().map(|array| array[i].clone()).collect()))
+                .collect();
+            RuntimeValue::Array(tuples)
+        })
+    );
+    fr.add_function(Function::builder("assertEquals")
+        .param(RuntimeType::Any)
+        .param(RuntimeType::Any)
+        .returns(RuntimeType::Null)
+        .build(|args| {
+            let actual = args.as_any(0);
+            let expected = args.as_any(1);
+            if actual != expected {
+                panic!("Assertion failed\n  expected: {}\n    actual: {}", render_quoted(expected), render_quoted(actual));
+            }
+            RuntimeValue::Null
+        })
+    );
+    fr.add_function(Function::builder("writeFile")
+        .param(RuntimeType::String)
+        .param(RuntimeType::String)
+        .returns(RuntimeType::Bool)
+        .build(|args| {
             let path = args.as_str(0);
             let contents = args.as_str(1);
             match fs::write(path, contents) {
@@ -91,62 +539,693 @@ pub fn load_native_functions(fr: &mut FunctionRegistry) {
                 Err(_) => RuntimeValue::Bool(false)
             }
         })
-    ));
-    fr.add_function(Function::new(
-        "readFile".to_string(),
-        ParamCount::Fixed(1),
-        vec![RuntimeType::String],
-        Box::new(|args| {
+    );
+    fr.add_function(Function::builder("readFile")
+        .param(RuntimeType::String)
+        .build(|args| {
             let path = args.as_str(0);
             match fs::read_to_string(path) {
-                Ok(content) => RuntimeValue::String(content),
+                Ok(content) => RuntimeValue::String(content.into()),
                 Err(_) => RuntimeValue::Null
             }
         })
-    ));
-    fr.add_function(Function::new(
-        "deleteFile".to_string(),
-        ParamCount::Fixed(1),
-        vec![RuntimeType::String],
-        Box::new(|args| {
+    );
+    fr.add_function(Function::builder("deleteFile")
+        .param(RuntimeType::String)
+        .returns(RuntimeType::Bool)
+        .build(|args| {
             let path = args.as_str(0);
             match fs::remove_file(path) {
                 Ok(_) => RuntimeValue::Bool(true),
                 Err(_) => RuntimeValue::Bool(false)
             }
         })
-    ));
-    fr.add_function(Function::new(
-        "exists".to_string(),
-        ParamCount::Fixed(1),
-        vec![RuntimeType::String],
-        Box::new(|args| {
+    );
+    fr.add_function(Function::builder("exists")
+        .param(RuntimeType::String)
+        .returns(RuntimeType::Bool)
+        .build(|args| {
             let path = args.as_str(0);
             RuntimeValue::Bool(Path::new(path).exists())
         })
-    ));
-    fr.add_function(Function::new(
-        "createDir".to_string(),
-        ParamCount::Fixed(1),
-        vec![RuntimeType::String],
-        Box::new(|args| {
+    );
+    fr.add_function(Function::builder("createDir")
+        .param(RuntimeType::String)
+        .returns(RuntimeType::Bool)
+        .build(|args| {
             let path = args.as_str(0);
             match fs::create_dir_all(path) {
                 Ok(_) => RuntimeValue::Bool(true),
                 Err(_) => RuntimeValue::Bool(false)
             }
         })
-    ));
-    fr.add_function(Function::new(
-        "deleteDir".to_string(),
-        ParamCount::Fixed(1),
-        vec![RuntimeType::String],
-        Box::new(|args| {
+    );
+    fr.add_function(Function::builder("deleteDir")
+        .param(RuntimeType::String)
+        .returns(RuntimeType::Bool)
+        .build(|args| {
             let path = args.as_str(0);
             match fs::remove_dir_all(path) {
                 Ok(_) => RuntimeValue::Bool(true),
                 Err(_) => RuntimeValue::Bool(false)
             }
         })
-    ));
-}
\ No newline at end of file
+    );
+}
+
+/// Checks that an array of `count` elements built by `function` stays
+/// within `MAX_ARRAY_LEN`.
+fn array_len(function: &str, count: f32) -> usize {
+    if count > MAX_ARRAY_LEN as f32 {
+        panic!("Function {} cannot build an array of {} elements, the maximum is {}", function, count, MAX_ARRAY_LEN);
+    }
+    count as usize
+}
+
+fn path_value(path: Option<PathBuf>) -> RuntimeValue {
+    match path {
+        Some(path) => RuntimeValue::String(path.to_string_lossy().into()),
+        None => RuntimeValue::Null
+    }
+}
+
+/// 32-bit FNV-1a hash of `value`, xor-folded to 24 bits so every result is
+/// exactly representable as an `f32`. Stable across runs and platforms.
+fn fnv_hash(value: &str) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in value.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    (hash >> 24) ^ (hash & 0xffffff)
+}
+
+/// Registers the natives of the optional `science` module.
+#[cfg(feature = "science")]
+fn load_science_functions(fr: &mut FunctionRegistry) {
+    fr.add_function(Function::builder("degToRad")
+        .param(RuntimeType::Number)
+        .returns(RuntimeType::Number)
+        .build(|args| RuntimeValue::Number(science::deg_to_rad(*args.as_f32(0))))
+    );
+    fr.add_function(Function::builder("radToDeg")
+        .param(RuntimeType::Number)
+        .returns(RuntimeType::Number)
+        .build(|args| RuntimeValue::Number(science::rad_to_deg(*args.as_f32(0))))
+    );
+    fr.add_function(Function::builder("factorial")
+        .param(RuntimeType::Number)
+        .returns(RuntimeType::Number)
+        .build(|args| {
+            let n = *args.as_f32(0);
+            match science::factorial(n) {
+                Some(result) => RuntimeValue::Number(result),
+                None => panic!("Function factorial expects a non-negative whole number, got {}", n)
+            }
+        })
+    );
+    for (name, function) in [("gcd", science::gcd as fn(f32, f32) -> Option<f32>), ("lcm", science::lcm)] {
+        fr.add_function(Function::builder(name)
+            .param(RuntimeType::Number)
+            .param(RuntimeType::Number)
+            .returns(RuntimeType::Number)
+            .build(move |args| {
+                let (a, b) = (*args.as_f32(0), *args.as_f32(1));
+                match function(a, b) {
+                    Some(result) => RuntimeValue::Number(result),
+                    None => panic!("Function {} expects whole numbers, got {} and {}", name, a, b)
+                }
+            })
+        );
+    }
+}
+
+/// Percent-encodes every byte of `value` except the unreserved characters of
+/// RFC 3986: letters, digits, `-`, `_`, `.` and `~`.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Reverses `percent_encode`. Returns `None` for a `%` not followed by two hex
+/// digits or when the decoded bytes are not valid UTF-8.
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            if !hex.chars().all(|char| char.is_ascii_hexdigit()) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Splits CSV text into rows of fields. Rows end at `\n` or `\r\n` and a
+/// trailing line break does not add an empty row. A field wrapped in `"` may
+/// contain commas and line breaks, and `""` inside it stands for one `"`.
+/// Quotes in the middle of an unquoted field are kept as they are.
+fn parse_csv(value: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut chars = value.chars().peekable();
+    let mut quoted = false;
+    while let Some(char) = chars.next() {
+        if quoted {
+            match char {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                },
+                '"' => quoted = false,
+                char => field.push(char)
+            }
+            continue;
+        }
+        match char {
+            '"' if field.is_empty() => quoted = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {},
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            },
+            char => field.push(char)
+        }
+    }
+    if quoted {
+        panic!("Unterminated quoted field in CSV");
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Renders a value the way it would be written in a script, quoting strings.
+fn render_quoted(value: &RuntimeValue) -> String {
+    match value {
+        RuntimeValue::String(value) => format!("{:?}", value),
+        value => value.to_string()
+    }
+}
+
+/// Formats `value` inserting `separator` between every group of three digits
+/// of its integer part.
+fn group_thousands(value: f32, separator: &str) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    let formatted = value.abs().to_string();
+    let (integer, fraction) = match formatted.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (formatted.as_str(), None)
+    };
+    let mut grouped = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push_str(separator);
+        }
+        grouped.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        grouped = format!("{}.{}", grouped, fraction);
+    }
+    if value < 0. {
+        grouped = format!("-{}", grouped);
+    }
+    grouped
+}
+
+/// Indexes selected by a Python-like `[start:end:step]` slice over `len`
+/// elements. Negative bounds count from the end and a negative step walks
+/// backwards from `start` down to, but excluding, `end`.
+fn slice_indexes(len: usize, start: isize, end: isize, step: isize) -> Vec<usize> {
+    let len = len as isize;
+    let resolve = |bound: isize, lowest: isize, highest: isize| {
+        let bound = if bound < 0 { bound + len } else { bound };
+        bound.clamp(lowest, highest)
+    };
+    let mut indexes = vec![];
+    if step > 0 {
+        let mut i = resolve(start, 0, len);
+        let end = resolve(end, 0, len);
+        while i < end {
+            indexes.push(i as usize);
+            i += step;
+        }
+    } else {
+        let mut i = resolve(start, -1, len - 1);
+        let end = resolve(end, -1, len - 1);
+        while i > end {
+            indexes.push(i as usize);
+            i += step;
+        }
+    }
+    indexes
+}
+
+/// Resolves a possibly negative index (counting from the end) against `len`,
+/// returning `None` when it falls outside the collection.
+fn wrap_index(index: isize, len: usize) -> Option<usize> {
+    let resolved = if index < 0 { len as isize + index } else { index };
+    if resolved < 0 || resolved >= len as isize {
+        return None;
+    }
+    Some(resolved as usize)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    use crate::lang::{func::function_registry::ParamCount, interpreter::ObjectMap, panics::catch};
+
+    fn numbers(values: &[f32]) -> RuntimeValue {
+        RuntimeValue::Array(values.iter().map(|value| RuntimeValue::Number(*value)).collect())
+    }
+
+    #[test]
+    fn test_min_max() {
+        let fr = FunctionRegistry::new();
+        let numbers = |values: &[f32]| values.iter().map(|value| RuntimeValue::Number(*value)).collect::<Vec<_>>();
+        assert_eq!(fr.call("max", numbers(&[1., 9., 3.])), RuntimeValue::Number(9.));
+        assert_eq!(fr.call("min", numbers(&[1., 9., -3.])), RuntimeValue::Number(-3.));
+        assert_eq!(fr.call("min", numbers(&[5.])), RuntimeValue::Number(5.));
+        assert_eq!(fr.call("max", numbers(&[5.])), RuntimeValue::Number(5.));
+        assert_eq!(fr.signature("min").unwrap().param_count, ParamCount::Dynamic(1));
+        let err = catch(|| fr.call("min", vec![])).unwrap_err();
+        assert_eq!(err.to_string(), "Function min expects 1 params as minimum, got 0");
+        assert!(catch(|| fr.call("min", vec![RuntimeValue::Number(1.), RuntimeValue::String("2".into())])).is_err());
+    }
+
+    #[test]
+    fn test_math_functions() {
+        let fr = FunctionRegistry::new();
+        let call = |name: &str, value: f32| fr.call(name, vec![RuntimeValue::Number(value)]);
+        assert_eq!(call("abs", -2.5), RuntimeValue::Number(2.5));
+        assert_eq!(call("abs", 3.), RuntimeValue::Number(3.));
+        assert_eq!(call("floor", -1.5), RuntimeValue::Number(-2.));
+        assert_eq!(call("floor", 1.9), RuntimeValue::Number(1.));
+        assert_eq!(call("ceil", 1.1), RuntimeValue::Number(2.));
+        assert_eq!(call("ceil", -1.9), RuntimeValue::Number(-1.));
+        assert_eq!(call("round", 2.5), RuntimeValue::Number(3.));
+        assert_eq!(call("round", -2.5), RuntimeValue::Number(-3.));
+        assert_eq!(call("round", 2.4), RuntimeValue::Number(2.));
+        assert_eq!(call("sqrt", 16.), RuntimeValue::Number(4.));
+        assert_eq!(call("sqrt", 0.), RuntimeValue::Number(0.));
+        let err = catch(|| call("sqrt", -1.)).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot take the square root of a negative number: sqrt(-1)");
+        assert!(catch(|| fr.call("abs", vec![RuntimeValue::String("1".into())])).is_err());
+    }
+
+    #[test]
+    fn test_to_int() {
+        let fr = FunctionRegistry::new();
+        assert_eq!(fr.call("toInt", vec![RuntimeValue::Number(3.9)]), RuntimeValue::Number(3.));
+        assert_eq!(fr.call("toInt", vec![RuntimeValue::Number(-3.9)]), RuntimeValue::Number(-3.));
+        assert_eq!(fr.call("toInt", vec![RuntimeValue::String(" 42.7".into())]), RuntimeValue::Number(42.));
+        let err = catch(|| fr.call("toInt", vec![RuntimeValue::String("abc".into())])).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot convert \"abc\" to an integer");
+        let err = catch(|| fr.call("toInt", vec![RuntimeValue::Number(f32::INFINITY)])).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot convert inf to an integer");
+        assert!(catch(|| fr.call("toInt", vec![RuntimeValue::Bool(true)])).is_err());
+    }
+
+    #[test]
+    fn test_at() {
+        let fr = FunctionRegistry::new();
+        let array = numbers(&[10., 20., 30.]);
+        assert_eq!(fr.call("at", vec![array.clone(), RuntimeValue::Number(1.)]), RuntimeValue::Number(20.));
+        assert_eq!(fr.call("at", vec![array.clone(), RuntimeValue::Number(3.)]), RuntimeValue::Null);
+        assert_eq!(fr.call("at", vec![array.clone(), RuntimeValue::Number(-1.)]), RuntimeValue::Number(30.));
+        assert_eq!(fr.call("at", vec![array.clone(), RuntimeValue::Number(-4.)]), RuntimeValue::Null);
+        assert!(catch(|| fr.call("at", vec![array.clone(), RuntimeValue::Number(1.5)])).is_err());
+        assert!(catch(|| fr.call("at", vec![array.clone(), RuntimeValue::Number(f32::NAN)])).is_err());
+        assert!(catch(|| fr.call("at", vec![array, RuntimeValue::Number(f32::INFINITY)])).is_err());
+
+        let string = RuntimeValue::String("héllo".into());
+        assert_eq!(fr.call("at", vec![string.clone(), RuntimeValue::Number(1.)]), RuntimeValue::String("é".into()));
+        assert_eq!(fr.call("at", vec![string.clone(), RuntimeValue::Number(-2.)]), RuntimeValue::String("l".into()));
+        assert_eq!(fr.call("at", vec![string, RuntimeValue::Number(9.)]), RuntimeValue::Null);
+    }
+
+    #[test]
+    fn test_case_conversion() {
+        let fr = FunctionRegistry::new();
+        let call = |name: &str, value: &str| fr.call(name, vec![RuntimeValue::String(value.into())]);
+        assert_eq!(call("uppercase", "ábc"), RuntimeValue::String("ÁBC".into()));
+        assert_eq!(call("lowercase", "ÁBC"), RuntimeValue::String("ábc".into()));
+        assert_eq!(call("uppercase", "straße"), RuntimeValue::String("STRASSE".into()));
+        assert_eq!(call("lowercase", "ΣΑΣ"), RuntimeValue::String("σας".into()));
+        assert_eq!(call("uppercase", ""), RuntimeValue::String("".into()));
+        assert!(catch(|| fr.call("uppercase", vec![RuntimeValue::Number(1.)])).is_err());
+    }
+
+    #[test]
+    fn test_trim_replace_contains() {
+        let fr = FunctionRegistry::new();
+        let string = |value: &str| RuntimeValue::String(value.into());
+        assert_eq!(fr.call("trim", vec![string(" \t hola mundo \n")]), string("hola mundo"));
+        assert_eq!(fr.call("trim", vec![string("   ")]), string(""));
+        assert_eq!(fr.call("replace", vec![string("a-b-c-"), string("-"), string(", ")]), string("a, b, c, "));
+        assert_eq!(fr.call("replace", vec![string("aaa"), string("aa"), string("b")]), string("ba"));
+        assert_eq!(fr.call("replace", vec![string("héllo"), string("é"), string("e")]), string("hello"));
+        assert_eq!(fr.call("replace", vec![string("abc"), string("x"), string("y")]), string("abc"));
+        assert!(catch(|| fr.call("replace", vec![string("abc"), string(""), string("y")])).is_err());
+        assert_eq!(fr.call("contains", vec![string("rusty script"), string("ty s")]), RuntimeValue::Bool(true));
+        assert_eq!(fr.call("contains", vec![string("rusty script"), string("Rusty")]), RuntimeValue::Bool(false));
+        assert_eq!(fr.call("contains", vec![string("abc"), string("")]), RuntimeValue::Bool(true));
+    }
+
+    #[test]
+    fn test_len_and_byte_length() {
+        let fr = FunctionRegistry::new();
+        let accented = RuntimeValue::String("é".into());
+        assert_eq!(fr.call("len", vec![accented.clone()]), RuntimeValue::Number(1.));
+        assert_eq!(fr.call("byteLength", vec![accented]), RuntimeValue::Number(2.));
+        assert_eq!(fr.call("len", vec![RuntimeValue::String("".into())]), RuntimeValue::Number(0.));
+        assert_eq!(fr.call("len", vec![numbers(&[1., 2., 3.])]), RuntimeValue::Number(3.));
+        assert!(catch(|| fr.call("len", vec![RuntimeValue::Number(1.)])).is_err());
+    }
+
+    #[test]
+    fn test_len_of_scalars() {
+        let fr = FunctionRegistry::new();
+        assert_eq!(fr.call("len", vec![RuntimeValue::String("hola".into())]), RuntimeValue::Number(4.));
+        let err = catch(|| fr.call("len", vec![RuntimeValue::Number(5.)])).unwrap_err();
+        assert_eq!(err.to_string(), "Function len expects an Array or a String, got Number");
+        let err = catch(|| fr.call("len", vec![RuntimeValue::Bool(true)])).unwrap_err();
+        assert_eq!(err.to_string(), "Function len expects an Array or a String, got Bool");
+        let err = catch(|| fr.call("len", vec![RuntimeValue::Null])).unwrap_err();
+        assert_eq!(err.to_string(), "Function len expects an Array or a String, got Null");
+    }
+
+    #[test]
+    fn test_fill_and_array() {
+        let fr = FunctionRegistry::new();
+        assert_eq!(fr.call("fill", vec![RuntimeValue::Number(0.), RuntimeValue::Number(3.)]), numbers(&[0., 0., 0.]));
+        assert_eq!(fr.call("fill", vec![RuntimeValue::Number(0.), RuntimeValue::Number(0.)]), numbers(&[]));
+        let err = catch(|| fr.call("fill", vec![RuntimeValue::Number(0.), RuntimeValue::Number(-1.)])).unwrap_err();
+        assert_eq!(err.to_string(), "Function fill expects a non-negative whole count, got -1");
+        for count in [1.5, f32::INFINITY, f32::NAN] {
+            assert!(catch(|| fr.call("fill", vec![RuntimeValue::Number(0.), RuntimeValue::Number(count)])).is_err());
+        }
+        let err = catch(|| fr.call("fill", vec![RuntimeValue::Number(0.), RuntimeValue::Number(1e12)])).unwrap_err();
+        assert_eq!(err.to_string(), format!("Function fill cannot build an array of 1000000000000 elements, the maximum is {}", MAX_ARRAY_LEN));
+        let max = fr.call("fill", vec![RuntimeValue::Null, RuntimeValue::Number(MAX_ARRAY_LEN as f32)]);
+        assert_eq!(fr.call("len", vec![max]), RuntimeValue::Number(MAX_ARRAY_LEN as f32));
+
+        let values = vec![RuntimeValue::Number(1.), RuntimeValue::Number(2.), RuntimeValue::Number(3.)];
+        assert_eq!(fr.call("array", values), numbers(&[1., 2., 3.]));
+        assert_eq!(fr.call("array", vec![]), numbers(&[]));
+    }
+
+    #[test]
+    fn test_split_and_join() {
+        let fr = FunctionRegistry::new();
+        let string = |value: &str| RuntimeValue::String(value.into());
+        let parts = fr.call("split", vec![string("a,b,c"), string(",")]);
+        assert_eq!(parts, RuntimeValue::Array(vec![string("a"), string("b"), string("c")]));
+        assert_eq!(fr.call("join", vec![parts, string(",")]), string("a,b,c"));
+
+        assert_eq!(fr.call("split", vec![string("héy"), string("")]), RuntimeValue::Array(vec![string("h"), string("é"), string("y")]));
+        assert_eq!(fr.call("split", vec![string(""), string(",")]), RuntimeValue::Array(vec![string("")]));
+        assert_eq!(fr.call("split", vec![string("a, b"), string(", ")]), RuntimeValue::Array(vec![string("a"), string("b")]));
+        assert_eq!(fr.call("split", vec![string(""), string("")]), RuntimeValue::Array(vec![]));
+
+        let mixed = RuntimeValue::Array(vec![RuntimeValue::Number(1.5), RuntimeValue::Bool(true), RuntimeValue::Null, string("x")]);
+        assert_eq!(fr.call("join", vec![mixed, string(" ")]), string("1.5 true null x"));
+        assert_eq!(fr.call("join", vec![RuntimeValue::Array(vec![]), string(",")]), string(""));
+        assert!(catch(|| fr.call("join", vec![string("abc"), string(",")])).is_err());
+    }
+
+    #[test]
+    fn test_hash() {
+        let fr = FunctionRegistry::new();
+        let hash = |value: &str| fr.call("hash", vec![RuntimeValue::String(value.into())]);
+        assert_eq!(hash("abc"), hash("abc"));
+        assert_ne!(hash("abc"), hash("abd"));
+        assert_eq!(fnv_hash("a"), 0xe4 ^ 0x0c292c);
+        assert_eq!(hash("abc"), RuntimeValue::Number(fnv_hash("abc") as f32));
+        assert!(fnv_hash("any string") < 1 << 24);
+    }
+
+    #[cfg(feature = "science")]
+    #[test]
+    fn test_science() {
+        let fr = FunctionRegistry::new();
+        let number = |value: f32| RuntimeValue::Number(value);
+        assert_eq!(fr.call("factorial", vec![number(5.)]), number(120.));
+        assert_eq!(fr.call("gcd", vec![number(12.), number(18.)]), number(6.));
+        assert_eq!(fr.call("lcm", vec![number(4.), number(6.)]), number(12.));
+        let RuntimeValue::Number(radians) = fr.call("degToRad", vec![number(180.)]) else { unreachable!() };
+        assert!((radians - std::f32::consts::PI).abs() < 1e-6);
+        assert_eq!(fr.call("radToDeg", vec![number(radians)]), number(180.));
+
+        let err = catch(|| fr.call("factorial", vec![number(-1.)])).unwrap_err();
+        assert_eq!(err.to_string(), "Function factorial expects a non-negative whole number, got -1");
+        assert!(catch(|| fr.call("factorial", vec![number(1.5)])).is_err());
+        let err = catch(|| fr.call("gcd", vec![number(1.5), number(3.)])).unwrap_err();
+        assert_eq!(err.to_string(), "Function gcd expects whole numbers, got 1.5 and 3");
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn test_base64() {
+        let fr = FunctionRegistry::new();
+        let call = |name: &str, value: &str| fr.call(name, vec![RuntimeValue::String(value.into())]);
+        let encoded = call("base64Encode", "héllo, world");
+        assert_eq!(encoded, RuntimeValue::String("aMOpbGxvLCB3b3JsZA==".into()));
+        assert_eq!(call("base64Decode", "aMOpbGxvLCB3b3JsZA=="), RuntimeValue::String("héllo, world".into()));
+        assert_eq!(call("base64Decode", "not base64!"), RuntimeValue::Null);
+        // valid base64 of bytes that are not UTF-8
+        assert_eq!(call("base64Decode", "/w=="), RuntimeValue::Null);
+        assert_eq!(call("base64Decode", "Zh=="), RuntimeValue::Null);
+    }
+
+    #[test]
+    fn test_escape_and_unescape() {
+        let fr = FunctionRegistry::new();
+        let call = |name: &str, value: &str| fr.call(name, vec![RuntimeValue::String(value.into())]);
+        let text = "line \"one\"\n\tline 'two' \\ end\r\0";
+        let escaped = call("escape", text);
+        assert_eq!(escaped, RuntimeValue::String(r#"line \"one\"\n\tline 'two' \\ end\r\0"#.into()));
+        assert_eq!(fr.call("unescape", vec![escaped]), RuntimeValue::String(text.into()));
+        assert_eq!(call("unescape", r"it\'s \
+joined"), RuntimeValue::String("it's joined".into()));
+        let err = catch(|| call("unescape", r"bad \q")).unwrap_err();
+        assert_eq!(err.to_string(), "Unknown escape sequence '\\q' at position 4");
+        let err = catch(|| call("unescape", r"end \")).unwrap_err();
+        assert_eq!(err.to_string(), "Unexpected end of input at position 5");
+    }
+
+    #[test]
+    fn test_url_encoding() {
+        let fr = FunctionRegistry::new();
+        let call = |name: &str, value: &str| fr.call(name, vec![RuntimeValue::String(value.into())]);
+        let text = "a b&c=d/é?~_.-";
+        let encoded = call("urlEncode", text);
+        assert_eq!(encoded, RuntimeValue::String("a%20b%26c%3Dd%2F%C3%A9%3F~_.-".into()));
+        assert_eq!(call("urlDecode", "a%20b%26c%3Dd%2F%C3%A9%3F~_.-"), RuntimeValue::String(text.into()));
+        assert_eq!(call("urlDecode", "100%25+ok"), RuntimeValue::String("100%+ok".into()));
+        assert_eq!(call("urlDecode", "bad%2"), RuntimeValue::Null);
+        assert_eq!(call("urlDecode", "bad%zz"), RuntimeValue::Null);
+        assert_eq!(call("urlDecode", "%+1"), RuntimeValue::Null);
+        assert_eq!(call("urlDecode", "%FF"), RuntimeValue::Null);
+    }
+
+    #[test]
+    fn test_range() {
+        let fr = FunctionRegistry::new();
+        let range = |args: &[f32]| fr.call("range", args.iter().map(|arg| RuntimeValue::Number(*arg)).collect()).to_string();
+        assert_eq!(range(&[0., 4.]), "[0, 1, 2, 3]");
+        assert_eq!(range(&[1., 8., 3.]), "[1, 4, 7]");
+        assert_eq!(range(&[5., 0., -1.]), "[5, 4, 3, 2, 1]");
+        assert_eq!(range(&[5., 0., -2.]), "[5, 3, 1]");
+        assert_eq!(range(&[3., 3., -1.]), "[]");
+        let err = catch(|| range(&[0., 5., -1.])).unwrap_err();
+        assert_eq!(err.to_string(), "Function range cannot reach 5 from 0 with a step of -1");
+        let err = catch(|| range(&[5., 0.])).unwrap_err();
+        assert_eq!(err.to_string(), "Function range cannot reach 0 from 5 with a step of 1");
+        let err = catch(|| range(&[0., 5., 0.])).unwrap_err();
+        assert_eq!(err.to_string(), "Function range expects a non-zero step, got 0");
+        let err = catch(|| range(&[0., f32::INFINITY])).unwrap_err();
+        assert_eq!(err.to_string(), "Function range expects finite numbers, got range(0, inf, 1)");
+        assert!(catch(|| range(&[0., 5., f32::NAN])).is_err());
+        let err = catch(|| range(&[0., 1e12])).unwrap_err();
+        assert_eq!(err.to_string(), format!("Function range cannot build an array of 1000000000000 elements, the maximum is {}", MAX_ARRAY_LEN));
+        assert!(catch(|| range(&[0., 1., 1e-9])).is_err());
+    }
+
+    #[test]
+    fn test_typeof() {
+        let fr = FunctionRegistry::new();
+        let cases = [
+            (RuntimeValue::Number(1.), "number"),
+            (RuntimeValue::String("a".into()), "string"),
+            (RuntimeValue::Bool(false), "bool"),
+            (RuntimeValue::Null, "null"),
+            (RuntimeValue::Array(vec![]), "array"),
+            (RuntimeValue::Object(ObjectMap::new()), "object"),
+            (RuntimeValue::Function("print".into()), "function"),
+        ];
+        for (value, name) in cases {
+            assert_eq!(fr.call("typeof", vec![value]), RuntimeValue::String(name.into()));
+        }
+        assert!(catch(|| fr.call("typeof", vec![])).is_err());
+    }
+
+    #[test]
+    fn test_types_of() {
+        let fr = FunctionRegistry::new();
+        let values = vec![RuntimeValue::Number(1.), RuntimeValue::String("a".into()), RuntimeValue::Bool(true), RuntimeValue::Array(vec![]), RuntimeValue::Null];
+        assert_eq!(fr.call("typesOf", vec![RuntimeValue::Array(values)]).to_string(), r#"["number", "string", "bool", "array", "null"]"#);
+        assert!(catch(|| fr.call("typesOf", vec![RuntimeValue::Number(1.)])).is_err());
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let fr = FunctionRegistry::new();
+        let call = |value: &str| fr.call("parseCsv", vec![RuntimeValue::String(value.into())]).to_string();
+        assert_eq!(call("a,b,c\n1,2,3\n"), r#"[["a", "b", "c"], ["1", "2", "3"]]"#);
+        assert_eq!(call("name,city\r\n\"Doe, John\",Paris"), r#"[["name", "city"], ["Doe, John", "Paris"]]"#);
+        assert_eq!(call("\"say \"\"hi\"\"\",,x\"y"), r#"[["say \"hi\"", "", "x\"y"]]"#);
+        assert_eq!(call("\"two\nlines\""), r#"[["two\nlines"]]"#);
+        assert_eq!(call(""), "[]");
+        let err = catch(|| fr.call("parseCsv", vec![RuntimeValue::String("\"open".into())])).unwrap_err();
+        assert_eq!(err.to_string(), "Unterminated quoted field in CSV");
+    }
+
+    #[test]
+    fn test_read_number() {
+        let fr = FunctionRegistry::new();
+        fr.state().set_input(Box::new(Cursor::new("42\nabc\n -1.5 \r\nnope\n")));
+        assert_eq!(fr.call("readNumber", vec![]), RuntimeValue::Number(42.));
+        assert_eq!(fr.call("readNumber", vec![]), RuntimeValue::Null);
+        assert_eq!(fr.call("readNumber", vec![RuntimeValue::Bool(true)]), RuntimeValue::Number(-1.5));
+        let err = catch(|| fr.call("readNumber", vec![RuntimeValue::Bool(true)])).unwrap_err();
+        assert_eq!(err.to_string(), "Function readNumber expected a number, got \"nope\"");
+    }
+
+    #[test]
+    fn test_script_path() {
+        let fr = FunctionRegistry::new();
+        assert_eq!(fr.call("scriptPath", vec![]), RuntimeValue::Null);
+        assert_eq!(fr.call("scriptDir", vec![]), RuntimeValue::Null);
+        fr.state().set_script_path(Some(PathBuf::from("scripts/main.rs")));
+        assert_eq!(fr.call("scriptPath", vec![]), RuntimeValue::String("scripts/main.rs".into()));
+        assert_eq!(fr.call("scriptDir", vec![]), RuntimeValue::String("scripts".into()));
+        fr.state().set_script_path(Some(PathBuf::from("main.rs")));
+        assert_eq!(fr.call("scriptDir", vec![]), RuntimeValue::String(".".into()));
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let fr = FunctionRegistry::new();
+        let pushed = fr.call("push", vec![numbers(&[1., 2.]), RuntimeValue::Number(3.)]);
+        assert_eq!(pushed, numbers(&[1., 2., 3.]));
+        assert_eq!(fr.call("push", vec![numbers(&[]), RuntimeValue::Null]), RuntimeValue::Array(vec![RuntimeValue::Null]));
+        assert_eq!(fr.call("pop", vec![pushed]), RuntimeValue::Number(3.));
+        assert_eq!(fr.call("pop", vec![numbers(&[])]), RuntimeValue::Null);
+        assert!(catch(|| fr.call("push", vec![RuntimeValue::Number(1.), RuntimeValue::Number(2.)])).is_err());
+    }
+
+    #[test]
+    fn test_chunk() {
+        let fr = FunctionRegistry::new();
+        let array = numbers(&[1., 2., 3., 4., 5.]);
+        assert_eq!(
+            fr.call("chunk", vec![array.clone(), RuntimeValue::Number(2.)]),
+            RuntimeValue::Array(vec![numbers(&[1., 2.]), numbers(&[3., 4.]), numbers(&[5.])])
+        );
+        assert_eq!(fr.call("chunk", vec![numbers(&[]), RuntimeValue::Number(3.)]), numbers(&[]));
+        assert!(catch(|| fr.call("chunk", vec![array.clone(), RuntimeValue::Number(0.)])).is_err());
+        assert!(catch(|| fr.call("chunk", vec![array.clone(), RuntimeValue::Number(-2.)])).is_err());
+        assert!(catch(|| fr.call("chunk", vec![array.clone(), RuntimeValue::Number(f32::NAN)])).is_err());
+        assert!(catch(|| fr.call("chunk", vec![array, RuntimeValue::Number(1.5)])).is_err());
+    }
+
+    #[test]
+    fn test_zip() {
+        let fr = FunctionRegistry::new();
+        let letters = RuntimeValue::Array(vec![RuntimeValue::String("a".into()), RuntimeValue::String("b".into())]);
+        let result = fr.call("zip", vec![numbers(&[1., 2., 3.]), letters.clone()]);
+        assert_eq!(result.to_string(), r#"[[1, "a"], [2, "b"]]"#);
+
+        let result = fr.call("zip", vec![numbers(&[1., 2.]), letters, numbers(&[3., 4., 5.])]);
+        assert_eq!(result.to_string(), r#"[[1, "a", 3], [2, "b", 4]]"#);
+        assert_eq!(fr.call("zip", vec![numbers(&[1.]), numbers(&[])]), numbers(&[]));
+    }
+
+    #[test]
+    fn test_format_number() {
+        let fr = FunctionRegistry::new();
+        let format = |value: f32| fr.call("formatNumber", vec![RuntimeValue::Number(value)]);
+        assert_eq!(format(1234567.), RuntimeValue::String("1,234,567".into()));
+        assert_eq!(format(999.), RuntimeValue::String("999".into()));
+        assert_eq!(format(1234.5), RuntimeValue::String("1,234.5".into()));
+        assert_eq!(format(-98765.25), RuntimeValue::String("-98,765.25".into()));
+        assert_eq!(format(-123.), RuntimeValue::String("-123".into()));
+        assert_eq!(
+            fr.call("formatNumber", vec![RuntimeValue::Number(1234567.), RuntimeValue::String(".".into())]),
+            RuntimeValue::String("1.234.567".into())
+        );
+    }
+
+    #[test]
+    fn test_assert_equals() {
+        let fr = FunctionRegistry::new();
+        assert_eq!(fr.call("assertEquals", vec![numbers(&[1., 2.]), numbers(&[1., 2.])]), RuntimeValue::Null);
+
+        let result = catch(|| fr.call("assertEquals", vec![numbers(&[1., 2.]), numbers(&[1., 3.])]));
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("expected: [1, 3]"));
+        assert!(message.contains("actual: [1, 2]"));
+
+        let result = catch(|| fr.call("assertEquals", vec![RuntimeValue::String("1".into()), RuntimeValue::Number(1.)]));
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains(r#"actual: "1""#));
+        assert!(message.contains("expected: 1"));
+    }
+
+    #[test]
+    fn test_slice() {
+        let fr = FunctionRegistry::new();
+        let slice = |args: Vec<f32>| {
+            let mut values = vec![RuntimeValue::String("abcdef".into())];
+            values.extend(args.into_iter().map(RuntimeValue::Number));
+            fr.call("slice", values)
+        };
+        assert_eq!(slice(vec![0., 6., 2.]), RuntimeValue::String("ace".into()));
+        assert_eq!(slice(vec![1., 4.]), RuntimeValue::String("bcd".into()));
+        assert_eq!(slice(vec![5., 0., -1.]), RuntimeValue::String("fedcb".into()));
+        assert_eq!(slice(vec![-1., -7., -1.]), RuntimeValue::String("fedcba".into()));
+        assert_eq!(slice(vec![-1., -7., -2.]), RuntimeValue::String("fdb".into()));
+        assert_eq!(slice(vec![4., 2.]), RuntimeValue::String("".into()));
+        assert!(catch(|| slice(vec![0., 6., 0.])).is_err());
+        assert_eq!(
+            fr.call("slice", vec![numbers(&[1., 2., 3., 4.]), RuntimeValue::Number(3.), RuntimeValue::Number(-5.), RuntimeValue::Number(-2.)]),
+            numbers(&[4., 2.])
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use std::{cell::{Cell, RefCell}, io::{self, BufRead, BufReader, Write}, path::{Path, PathBuf}, rc::Rc};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::lang::{error::RuntimeError, interpreter::RuntimeValue, panics::runtime_error};
+
+/// State shared by the native functions that talk to the host: where `print`
+/// and `printErr` write, where `read` reads from, the random number generator
+/// and the path of the script being run.
+pub struct NativeState {
+    output: RefCell<Box<dyn Write>>,
+    error_output: RefCell<Box<dyn Write>>,
+    input: RefCell<Box<dyn BufRead>>,
+    rng: RefCell<StdRng>,
+    display_precision: Cell<Option<usize>>,
+    max_output_bytes: Cell<Option<usize>>,
+    output_bytes: Cell<usize>,
+    script_path: RefCell<Option<PathBuf>>
+}
+
+impl Default for NativeState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NativeState {
+    pub fn new() -> Self {
+        Self {
+            output: RefCell::new(Box::new(io::stdout())),
+            error_output: RefCell::new(Box::new(io::stderr())),
+            input: RefCell::new(Box::new(BufReader::new(io::stdin()))),
+            rng: RefCell::new(StdRng::from_os_rng()),
+            display_precision: Cell::new(None),
+            max_output_bytes: Cell::new(None),
+            output_bytes: Cell::new(0),
+            script_path: RefCell::new(None)
+        }
+    }
+
+    pub fn set_output(&self, output: Box<dyn Write>) {
+        *self.output.borrow_mut() = output;
+    }
+
+    pub fn set_error_output(&self, error_output: Box<dyn Write>) {
+        *self.error_output.borrow_mut() = error_output;
+    }
+
+    pub fn set_input(&self, input: Box<dyn BufRead>) {
+        *self.input.borrow_mut() = input;
+    }
+
+    pub fn seed(&self, seed: u64) {
+        *self.rng.borrow_mut() = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn set_display_precision(&self, precision: Option<usize>) {
+        self.display_precision.set(precision);
+    }
+
+    /// Caps the bytes `write_line` may write from now on. `None` lifts the cap.
+    pub fn set_max_output_bytes(&self, max: Option<usize>) {
+        self.max_output_bytes.set(max);
+        self.output_bytes.set(0);
+    }
+
+    pub fn set_script_path(&self, path: Option<PathBuf>) {
+        *self.script_path.borrow_mut() = path;
+    }
+
+    pub fn script_path(&self) -> Option<PathBuf> {
+        self.script_path.borrow().clone()
+    }
+
+    /// The directory holding the running script, `.` for a bare file name.
+    pub fn script_dir(&self) -> Option<PathBuf> {
+        self.script_path.borrow().as_deref().map(|path| match path.parent() {
+            Some(dir) if dir != Path::new("") => dir.to_path_buf(),
+            _ => PathBuf::from(".")
+        })
+    }
+
+    /// Turns a value into the text natives show to the user, applying the
+    /// display precision to numbers.
+    pub fn display(&self, value: &RuntimeValue) -> String {
+        match self.display_precision.get() {
+            Some(precision) => format!("{:.*}", precision, value),
+            None => value.to_string()
+        }
+    }
+
+    /// Fails without writing anything when the line would take the output
+    /// past its cap.
+    pub fn write_line(&self, text: &str) {
+        let written = self.output_bytes.get() + text.len() + 1;
+        if let Some(max) = self.max_output_bytes.get() && written > max {
+            runtime_error(RuntimeError::OutputLimitExceeded(max));
+        }
+        self.output_bytes.set(written);
+        writeln!(self.output.borrow_mut(), "{}", text).expect("Error when writing to the output");
+    }
+
+    pub fn write_error_line(&self, text: &str) {
+        writeln!(self.error_output.borrow_mut(), "{}", text).expect("Error when writing to the error output");
+    }
+
+    pub fn read_line(&self) -> String {
+        let mut value = String::new();
+        self.input.borrow_mut().read_line(&mut value).expect("Error when reading from console");
+        if value.ends_with("\n") {
+            value.pop();
+        }
+        value
+    }
+
+    pub fn random(&self) -> f32 {
+        self.rng.borrow_mut().random::<f32>()
+    }
+}
+
+/// A cloneable in-memory writer, handy for capturing script output.
+#[derive(Clone, Default)]
+pub struct SharedBuffer {
+    bytes: Rc<RefCell<Vec<u8>>>
+}
+
+impl SharedBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.bytes.borrow()).into_owned()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+//! Number helpers for teaching scripts, backing the `degToRad`, `radToDeg`,
+//! `factorial`, `gcd` and `lcm` natives. Functions taking whole numbers
+//! return `None` when given anything else.
+
+use std::f32::consts::PI;
+
+pub fn deg_to_rad(degrees: f32) -> f32 {
+    degrees * PI / 180.
+}
+
+pub fn rad_to_deg(radians: f32) -> f32 {
+    radians * 180. / PI
+}
+
+/// `n!` for a non-negative whole `n`, infinite once it no longer fits.
+pub fn factorial(n: f32) -> Option<f32> {
+    if n < 0. || n.fract() != 0. {
+        return None;
+    }
+    // 35! is past f32::MAX, stop before looping over a huge `n`
+    if n > 34. {
+        return Some(f32::INFINITY);
+    }
+    Some((1..=n as u32).map(|i| i as f32).product())
+}
+
+/// Greatest common divisor of two whole numbers, ignoring their signs.
+pub fn gcd(a: f32, b: f32) -> Option<f32> {
+    if !is_whole(a) || !is_whole(b) {
+        return None;
+    }
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0. {
+        (a, b) = (b, a % b);
+    }
+    Some(a)
+}
+
+/// Least common multiple of two whole numbers, zero when either is zero.
+pub fn lcm(a: f32, b: f32) -> Option<f32> {
+    let divisor = gcd(a, b)?;
+    if divisor == 0. {
+        return Some(0.);
+    }
+    Some((a / divisor * b).abs())
+}
+
+fn is_whole(n: f32) -> bool {
+    n.is_finite() && n.fract() == 0.
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_factorial() {
+        assert_eq!(factorial(0.), Some(1.));
+        assert_eq!(factorial(5.), Some(120.));
+        assert_eq!(factorial(40.), Some(f32::INFINITY));
+        assert_eq!(factorial(-1.), None);
+        assert_eq!(factorial(2.5), None);
+    }
+
+    #[test]
+    fn test_gcd_lcm() {
+        assert_eq!(gcd(12., 18.), Some(6.));
+        assert_eq!(gcd(-12., 18.), Some(6.));
+        assert_eq!(gcd(7., 0.), Some(7.));
+        assert_eq!(gcd(0., 0.), Some(0.));
+        assert_eq!(gcd(1.5, 3.), None);
+        assert_eq!(lcm(4., 6.), Some(12.));
+        assert_eq!(lcm(-4., 6.), Some(12.));
+        assert_eq!(lcm(0., 6.), Some(0.));
+        assert_eq!(lcm(4., f32::INFINITY), None);
+    }
+}
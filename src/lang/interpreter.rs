@@ -1,10 +1,60 @@
-use std::{cmp::Ordering, ops::{Add, Div, Mul, Sub}, rc::Rc};
+use std::{collections::HashMap, fmt::{self, Display}, io::{BufRead, Write}, ops::{Add, Div, Mul, RangeInclusive, Rem, Sub}, path::PathBuf, rc::Rc};
 
-use super::{env::Env, func::function_registry::{FunctionRegistry, RuntimeType}, parser::{ASTNode, Parser}};
+use super::{checker::{Checker, Diagnostic}, env::Env, error::RuntimeError, func::function_registry::{Function, FunctionRegistry, RuntimeType}, panics::{catch, runtime_error}, parser::{arity, ASTNode, Parser, Pattern}};
+
+/// Native functions that can be called as `value.name(args)` on a string,
+/// receiving the string as their first argument.
+const STRING_METHODS: [&str; 4] = ["trim", "toLower", "toUpper", "substring"];
+
+/// Functions implemented by the interpreter itself because they call back into
+/// script code, with the number of arguments they take.
+const INTRINSICS: [(&str, usize); 2] = [("assertThrows", 1), ("dumpEnv", 0)];
+
+/// The implementation of a function registered with `add_lazy_function`.
+type LazyFunction = Rc<dyn Fn(&mut LazyArgs) -> RuntimeValue>;
+
+/// Default for the longest string, in bytes, that repetition may produce.
+pub const DEFAULT_MAX_STRING_LEN: usize = 16 * 1024 * 1024;
+
+/// A pending jump out of the statements being executed. Blocks stop as soon as
+/// one is set and the construct it targets consumes it.
+enum Flow {
+    Return(RuntimeValue),
+    Break(Option<String>),
+    Continue(Option<String>)
+}
 
 pub struct Interpreter {
     env: Env,
     fr: FunctionRegistry,
+    functions: HashMap<String, Rc<ASTNode>>,
+    lazy_functions: HashMap<String, LazyFunction>,
+    flow: Option<Flow>,
+    thrown: Option<RuntimeValue>,
+    steps: usize,
+    step_limit: Option<usize>,
+    allow_shadowing: bool,
+    loose_conditions: bool,
+    max_string_len: usize,
+}
+
+/// Options applied when building an interpreter with `Interpreter::with_options`.
+/// Every field left as `None` keeps the behavior of `Interpreter::new`.
+#[derive(Default)]
+pub struct InterpreterOptions {
+    /// Maximum number of nodes evaluated by a single `run` or `eval_*` call.
+    pub step_limit: Option<usize>,
+    /// Seed for the generator behind `random`, making its sequence reproducible.
+    pub random_seed: Option<u64>,
+    /// Where `print` writes to instead of stdout.
+    pub output: Option<Box<dyn Write>>,
+    /// Where `printErr` and `eprint` write to instead of stderr.
+    pub error_output: Option<Box<dyn Write>>,
+    /// Where `read` reads lines from instead of stdin.
+    pub input: Option<Box<dyn BufRead>>,
+    /// Lets a `let` in an inner scope shadow a variable of an outer scope.
+    /// Redeclaring a variable in the same scope is always an error.
+    pub allow_shadowing: bool,
 }
 
 impl Interpreter {
@@ -12,61 +62,498 @@ impl Interpreter {
     pub fn new() -> Self {
         Interpreter {
             env: Env::new(None),
-            fr: FunctionRegistry::new()
+            fr: FunctionRegistry::new(),
+            functions: HashMap::new(),
+            lazy_functions: HashMap::new(),
+            flow: None,
+            thrown: None,
+            steps: 0,
+            step_limit: None,
+            allow_shadowing: false,
+            loose_conditions: false,
+            max_string_len: DEFAULT_MAX_STRING_LEN
+        }
+    }
+
+    pub fn with_options(options: InterpreterOptions) -> Self {
+        let mut interpreter = Self::new();
+        interpreter.step_limit = options.step_limit;
+        interpreter.allow_shadowing = options.allow_shadowing;
+        let state = interpreter.fr.state();
+        if let Some(seed) = options.random_seed {
+            state.seed(seed);
+        }
+        if let Some(output) = options.output {
+            state.set_output(output);
+        }
+        if let Some(error_output) = options.error_output {
+            state.set_error_output(error_output);
+        }
+        if let Some(input) = options.input {
+            state.set_input(input);
+        }
+        interpreter
+    }
+
+    /// When enabled, conditions that are not a `Bool` are coerced with the same
+    /// truthiness rules as `toBool` instead of being rejected. This covers the
+    /// conditions of `if`, `while`, `for` and `do-while`, the ternary operator
+    /// and the operands of `&&` and `||`.
+    pub fn set_loose_conditions(&mut self, loose: bool) {
+        self.loose_conditions = loose;
+    }
+
+    /// Caps the length in bytes of strings built by repetition (`"x" * n`),
+    /// so untrusted scripts cannot exhaust memory.
+    pub fn set_max_string_len(&mut self, max: usize) {
+        self.max_string_len = max;
+    }
+
+    /// Rounds numbers to `precision` decimals when `print`, `printErr` and
+    /// `toString` turn them into text. `None` prints them in full.
+    pub fn set_display_precision(&mut self, precision: Option<usize>) {
+        self.fr.state().set_display_precision(precision);
+    }
+
+    /// Caps the total bytes `print` and `printJson` write from now on, failing
+    /// with `OutputLimitExceeded` instead of writing past it. `try`/`catch`
+    /// cannot catch that error. `None`, the default, leaves output unlimited.
+    pub fn set_max_output_bytes(&mut self, max: Option<usize>) {
+        self.fr.state().set_max_output_bytes(max);
+    }
+
+    /// Sets the file `scriptPath` and `scriptDir` report. Without one, as in
+    /// the REPL, both return null.
+    pub fn set_script_path(&mut self, path: Option<PathBuf>) {
+        self.fr.state().set_script_path(path);
+    }
+
+    pub fn add_function(&mut self, function: Function) {
+        self.fr.add_function(function);
+    }
+
+    /// Registers a native function that receives its arguments unevaluated,
+    /// so it decides which of them to evaluate and when, like a control flow
+    /// construct. It takes any number of arguments; spreading into it is not
+    /// supported. Functions declared with `fn` still take precedence.
+    pub fn add_lazy_function(&mut self, name: &str, implementation: impl Fn(&mut LazyArgs) -> RuntimeValue + 'static) {
+        self.lazy_functions.insert(name.to_string(), Rc::new(implementation));
+    }
+
+    /// Every function a script can call, sorted by name.
+    pub fn function_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.functions.keys()
+            .chain(self.lazy_functions.keys())
+            .chain(self.fr.names())
+            .cloned()
+            .chain(INTRINSICS.iter().map(|(name, _)| name.to_string()))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// The variables declared by scripts run so far, sorted by name.
+    pub fn variable_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.env.names().into_iter().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Renders every variable visible from the current scope with its value,
+    /// one block per scope from the innermost to the global one. Variables
+    /// are sorted by name so the dump is stable.
+    pub fn dump_env(&self) -> String {
+        let scopes = self.env.scopes();
+        let mut dump = String::new();
+        for (depth, vars) in scopes.iter().enumerate().map(|(i, vars)| (scopes.len() - 1 - i, vars)) {
+            let label = if depth == 0 { "global".to_string() } else { format!("scope {}", depth) };
+            if vars.is_empty() {
+                dump.push_str(&format!("{} {{}}\n", label));
+                continue;
+            }
+            dump.push_str(&format!("{} {{\n", label));
+            for (name, value) in vars {
+                let value = match value {
+                    RuntimeValue::String(value) => format!("{:?}", value),
+                    value => value.to_string()
+                };
+                dump.push_str(&format!("  {}: {}\n", name, value));
+            }
+            dump.push_str("}\n");
         }
+        dump
+    }
+
+    /// The value of a variable declared by a script, if there is one.
+    pub fn variable(&self, name: &str) -> Option<&RuntimeValue> {
+        self.env.is_declared(name).then(|| self.env.get(name))
+    }
+
+    /// Drops all script state: variables and functions declared with `fn`.
+    /// Functions registered through `add_function`, the native functions and
+    /// the options the interpreter was built with are kept.
+    pub fn reset(&mut self) {
+        self.env = Env::new(None);
+        self.functions.clear();
+        self.flow = None;
+        self.steps = 0;
     }
 
     pub fn run(&mut self, input: &str) -> RuntimeValue {
         let ast = Parser::new(input).parse();
+        self.steps = 0;
+        let last_value = self.execute_block(&ast);
+        match self.flow.take() {
+            Some(Flow::Return(value)) => value,
+            _ => last_value
+        }
+    }
+
+    /// Like `run` but returns the message of a syntax or runtime error instead
+    /// of panicking. Scopes left open by the failed statement are dropped.
+    pub fn try_run(&mut self, input: &str) -> Result<RuntimeValue, String> {
+        self.run_checked(input).map_err(|error| error.to_string())
+    }
+
+    /// Like `try_run` but keeps the error itself, so callers can point at
+    /// where in `input` a syntax error is.
+    pub fn run_checked(&mut self, input: &str) -> Result<RuntimeValue, RuntimeError> {
+        self.guarded(|interpreter| interpreter.run(input))
+    }
+
+    /// Runs `input` and returns the value of every top-level statement, with
+    /// `Null` for declarations and loops. A top-level `return` ends the run and
+    /// its value stands for that statement.
+    pub fn run_collect(&mut self, input: &str) -> Result<Vec<RuntimeValue>, RuntimeError> {
+        self.steps = 0;
+        self.guarded(|interpreter| {
+            let ast = Parser::new(input).parse();
+            interpreter.hoist_functions(&ast);
+            let mut values = vec![];
+            for node in ast {
+                let value = interpreter.initial_expression(node);
+                if let Some(Flow::Return(value)) = interpreter.flow.take() {
+                    values.push(value);
+                    break;
+                }
+                values.push(value);
+            }
+            values
+        })
+    }
+
+    /// Calls a script-defined or native function from the host with already
+    /// evaluated arguments.
+    pub fn call_function(&mut self, name: &str, args: Vec<RuntimeValue>) -> Result<RuntimeValue, RuntimeError> {
+        self.steps = 0;
+        self.guarded(|interpreter| interpreter.call(name, args))
+    }
+
+    /// Runs `f`, turning a panic into a `RuntimeError` and dropping the scopes
+    /// and pending flow it left behind.
+    fn guarded<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> Result<T, RuntimeError> {
+        let depth = self.env.depth();
+        let result = catch(|| f(self));
+        if result.is_err() {
+            while self.env.depth() > depth {
+                self.env.pop_scope();
+            }
+            self.flow = None;
+        }
+        result
+    }
+
+    fn execute_block(&mut self, nodes: &[Rc<ASTNode>]) -> RuntimeValue {
+        self.hoist_functions(nodes);
         let mut last_value = RuntimeValue::Null;
-        for node in ast {
-            last_value = self.initial_expression(node);
+        for node in nodes {
+            last_value = self.initial_expression(Rc::clone(node));
+            if self.flow.is_some() {
+                break;
+            }
         }
         last_value
     }
 
+    /// Registers every function declared directly in `nodes` before any of them
+    /// runs, so a function can be called above its declaration.
+    fn hoist_functions(&mut self, nodes: &[Rc<ASTNode>]) {
+        for node in nodes {
+            if let ASTNode::FunctionDeclaration { name, .. } = node.as_ref() {
+                self.functions.insert(name.to_owned(), Rc::clone(node));
+            }
+        }
+    }
+
+    /// Reports the statically knowable problems of `input` without running it.
+    /// A syntax error is reported as a single diagnostic.
+    pub fn check(&self, input: &str) -> Vec<Diagnostic> {
+        let ast = match catch(|| Parser::new(input).parse()) {
+            Ok(ast) => ast,
+            Err(error) => return vec![Diagnostic { message: error.to_string() }]
+        };
+        let mut functions: HashMap<String, RangeInclusive<usize>> = INTRINSICS.iter()
+            .map(|(name, param_count)| (name.to_string(), *param_count..=*param_count))
+            .collect();
+        functions.extend(self.lazy_functions.keys().map(|name| (name.to_owned(), 0..=usize::MAX)));
+        functions.extend(self.functions.iter().map(|(name, function)| {
+            match function.as_ref() {
+                ASTNode::FunctionDeclaration { params, .. } => (name.to_owned(), arity(params)),
+                _ => unreachable!("Expected FunctionDeclaration node!")
+            }
+        }));
+        Checker::new(&self.fr, functions).check(&ast)
+    }
+
+    pub fn eval_number(&mut self, input: &str) -> Result<f32, RuntimeError> {
+        self.eval_as(input, RuntimeType::Number, |value| match value {
+            RuntimeValue::Number(value) => Some(value),
+            _ => None
+        })
+    }
+
+    pub fn eval_string(&mut self, input: &str) -> Result<String, RuntimeError> {
+        self.eval_as(input, RuntimeType::String, |value| match value {
+            RuntimeValue::String(value) => Some(value.to_string()),
+            _ => None
+        })
+    }
+
+    pub fn eval_bool(&mut self, input: &str) -> Result<bool, RuntimeError> {
+        self.eval_as(input, RuntimeType::Bool, |value| match value {
+            RuntimeValue::Bool(value) => Some(value),
+            _ => None
+        })
+    }
+
+    fn eval_as<T>(&mut self, input: &str, expected: RuntimeType, extractor: fn(RuntimeValue) -> Option<T>) -> Result<T, RuntimeError> {
+        self.steps = 0;
+        let value = self.guarded(|interpreter| {
+            let expression = Parser::new(input).parse_expression();
+            interpreter.initial_expression(expression)
+        })?;
+        let found = value.to_type();
+        extractor(value).ok_or(RuntimeError::TypeMismatch { expected, found })
+    }
+
     fn initial_expression(&mut self, node: Rc<ASTNode>) -> RuntimeValue {
+        self.count_step();
         match node.as_ref() {
             ASTNode::Number(value) => RuntimeValue::Number(*value),
-            ASTNode::String(value) => RuntimeValue::String(value.clone()),
+            ASTNode::String(value) => RuntimeValue::String(Rc::clone(value)),
             ASTNode::Bool(value) => RuntimeValue::Bool(*value),
+            ASTNode::Null => RuntimeValue::Null,
+            ASTNode::ArrayLiteral { elements } => RuntimeValue::Array(
+                elements.iter().map(|node| self.initial_expression(Rc::clone(node))).collect()
+            ),
+            ASTNode::ObjectLiteral { entries } => RuntimeValue::Object(
+                entries.iter().map(|(key, node)| (key.to_owned(), self.initial_expression(Rc::clone(node)))).collect()
+            ),
             ASTNode::BinaryExpression { .. } => self.binary_expression(node),
             ASTNode::UnaryExpression { .. } => self.unary_expression(node),
+            ASTNode::ConditionalExpression { .. } => self.conditional_expression(node),
             ASTNode::FunctionCall { .. } => self.function_call(node),
+            ASTNode::MethodCall { .. } => self.method_call(node),
+            ASTNode::Index { target, index } => {
+                let target = self.initial_expression(Rc::clone(target));
+                let index = self.initial_expression(Rc::clone(index));
+                element_at(target, index)
+            },
+            ASTNode::Spread { .. } => panic!("Spread is only allowed in argument lists"),
             ASTNode::VarDeclaration { .. } => self.var_declaration(node),
             ASTNode::VarAssignment { .. } => self.var_assignment(node),
-            ASTNode::Identifier { name } => self.env.get(name).clone(),
+            ASTNode::Identifier { name } => self.identifier(name),
             ASTNode::IfStmt { .. } => self.if_stmt(node),
+            ASTNode::WhileStmt { .. } => self.while_stmt(node),
+            ASTNode::DoWhileStmt { .. } => self.do_while_stmt(node),
+            ASTNode::ForStmt { .. } => self.for_stmt(node),
+            ASTNode::TryCatch { .. } => self.try_catch(node),
+            ASTNode::FunctionDeclaration { .. } => self.function_declaration(node),
+            ASTNode::ReturnStmt { .. } => self.return_stmt(node),
+            ASTNode::Break { label } => {
+                self.flow = Some(Flow::Break(label.clone()));
+                RuntimeValue::Null
+            },
+            ASTNode::Continue { label } => {
+                self.flow = Some(Flow::Continue(label.clone()));
+                RuntimeValue::Null
+            },
+            ASTNode::Throw { value } => {
+                let value = self.initial_expression(Rc::clone(value));
+                let text = value.to_string();
+                self.thrown = Some(value);
+                runtime_error(RuntimeError::Thrown(text))
+            },
+        }
+    }
+
+    fn count_step(&mut self) {
+        self.steps += 1;
+        if let Some(limit) = self.step_limit && self.steps > limit {
+            runtime_error(RuntimeError::StepLimitExceeded(limit));
         }
     }
 
     fn if_stmt(&mut self, node: Rc<ASTNode>) -> RuntimeValue {
         if let ASTNode::IfStmt { expr, true_block, false_block } = node.as_ref() {
             let condition_value = self.initial_expression(Rc::clone(expr));
-            match condition_value {
-                RuntimeValue::Bool(condition_value) => {
-                    let mut last_value : RuntimeValue = RuntimeValue::Null;
-                    if condition_value {
-                        for node in true_block {
-                            last_value = self.initial_expression(Rc::clone(node));
-                        }
-                    } else if let Some(false_content) = false_block {
-                        for node in false_content {
-                            last_value = self.initial_expression(Rc::clone(node))
-                        }
+            let block = if self.expect_bool(condition_value, "if") {
+                true_block
+            } else if let Some(false_content) = false_block {
+                false_content
+            } else {
+                return RuntimeValue::Null;
+            };
+            // the block gets its own scope so its declarations don't leak out
+            self.env.push_scope();
+            let value = self.execute_block(block);
+            self.env.pop_scope();
+            return value;
+        }
+        unreachable!("Expected IfStmt node!")
+    }
+
+    fn while_stmt(&mut self, node: Rc<ASTNode>) -> RuntimeValue {
+        if let ASTNode::WhileStmt { label, condition, body } = node.as_ref() {
+            loop {
+                let condition_value = self.initial_expression(Rc::clone(condition));
+                if !self.expect_bool(condition_value, "while") {
+                    break;
+                }
+                self.env.push_scope();
+                self.execute_block(body);
+                self.env.pop_scope();
+                if self.stops_loop(label) {
+                    break;
+                }
+            }
+            return RuntimeValue::Null;
+        }
+        unreachable!("Expected WhileStmt node!")
+    }
+
+    /// Runs `init` in a scope of its own, so the loop variable is not visible
+    /// after the loop, and the body in a fresh child scope every iteration.
+    fn for_stmt(&mut self, node: Rc<ASTNode>) -> RuntimeValue {
+        if let ASTNode::ForStmt { label, init, condition, update, body } = node.as_ref() {
+            self.env.push_scope();
+            if let Some(init) = init {
+                self.initial_expression(Rc::clone(init));
+            }
+            loop {
+                if let Some(condition) = condition {
+                    let condition_value = self.initial_expression(Rc::clone(condition));
+                    if !self.expect_bool(condition_value, "for") {
+                        break;
                     }
-                    return last_value
                 }
-                _ => panic!("Expression inside if must return a bool value")
+                self.env.push_scope();
+                self.execute_block(body);
+                self.env.pop_scope();
+                if self.stops_loop(label) {
+                    break;
+                }
+                if let Some(update) = update {
+                    self.initial_expression(Rc::clone(update));
+                }
             }
+            self.env.pop_scope();
+            return RuntimeValue::Null;
+        }
+        unreachable!("Expected ForStmt node!")
+    }
+
+    fn do_while_stmt(&mut self, node: Rc<ASTNode>) -> RuntimeValue {
+        if let ASTNode::DoWhileStmt { label, body, condition } = node.as_ref() {
+            loop {
+                self.env.push_scope();
+                self.execute_block(body);
+                self.env.pop_scope();
+                if self.stops_loop(label) {
+                    break;
+                }
+                let condition_value = self.initial_expression(Rc::clone(condition));
+                if !self.expect_bool(condition_value, "do-while") {
+                    break;
+                }
+            }
+            return RuntimeValue::Null;
+        }
+        unreachable!("Expected DoWhileStmt node!")
+    }
+
+    /// Whether the loop labeled `label` has to stop after running its body.
+    /// A `break` or `continue` aimed at the loop is consumed here; any other
+    /// pending flow stops the loop and is left for an enclosing construct.
+    fn stops_loop(&mut self, label: &Option<String>) -> bool {
+        let targets_loop = |target: &Option<String>| target.is_none() || target == label;
+        match &self.flow {
+            None => false,
+            Some(Flow::Continue(target)) if targets_loop(target) => {
+                self.flow = None;
+                false
+            },
+            Some(Flow::Break(target)) if targets_loop(target) => {
+                self.flow = None;
+                true
+            },
+            Some(..) => true
+        }
+    }
+
+    /// Runs the try block and falls back to the catch block when it raises a
+    /// runtime error. Hitting the step limit is not catchable, so a script
+    /// cannot use `try` to run forever.
+    fn try_catch(&mut self, node: Rc<ASTNode>) -> RuntimeValue {
+        if let ASTNode::TryCatch { try_block, error_name, catch_block } = node.as_ref() {
+            let result = self.guarded(|interpreter| {
+                interpreter.env.push_scope();
+                let value = interpreter.execute_block(try_block);
+                interpreter.env.pop_scope();
+                value
+            });
+            return match result {
+                Ok(value) => value,
+                Err(error @ (RuntimeError::StepLimitExceeded(..) | RuntimeError::OutputLimitExceeded(..))) => runtime_error(error),
+                Err(error) => {
+                    let caught = match (error, self.thrown.take()) {
+                        (RuntimeError::Thrown(..), Some(value)) => value,
+                        (error, _) => RuntimeValue::String(error.to_string().into())
+                    };
+                    if !self.allow_shadowing && self.env.is_declared(error_name) {
+                        runtime_error(RuntimeError::AlreadyDeclared(error_name.to_owned()));
+                    }
+                    self.env.push_scope();
+                    self.env.add(error_name.to_owned(), caught);
+                    let value = self.execute_block(catch_block);
+                    self.env.pop_scope();
+                    value
+                }
+            };
+        }
+        unreachable!("Expected TryCatch node!")
+    }
+
+    /// Turns the value of the condition of `construct` into a `bool`, coercing
+    /// it when loose conditions are enabled.
+    fn expect_bool(&self, value: RuntimeValue, construct: &str) -> bool {
+        match value {
+            RuntimeValue::Bool(value) => value,
+            value if self.loose_conditions => value.is_truthy(),
+            _ => panic!("Expression inside {} must return a bool value", construct)
         }
-        unreachable!("Expected IfStmt node!")
     }
 
     fn var_declaration(&mut self, node: Rc<ASTNode>) -> RuntimeValue {
-        if let ASTNode::VarDeclaration { name, value } = node.as_ref() {
+        if let ASTNode::VarDeclaration { pattern, value } = node.as_ref() {
+            for name in pattern.names() {
+                if !self.allow_shadowing && self.env.is_declared(name) {
+                    runtime_error(RuntimeError::AlreadyDeclared(name.to_owned()));
+                }
+            }
             let var_value = self.initial_expression(Rc::clone(value));
-            self.env.add(name.to_owned(), var_value);
+            self.bind_pattern(pattern, var_value);
             return RuntimeValue::Null;
         }
         unreachable!("Expected VarDeclaration node!");
@@ -75,31 +562,89 @@ impl Interpreter {
     fn var_assignment(&mut self, node: Rc<ASTNode>) -> RuntimeValue {
         if let ASTNode::VarAssignment { name, value } = node.as_ref() {
             let var_value = self.initial_expression(Rc::clone(value));
-            self.env.update(name.to_owned(), var_value);
-            return RuntimeValue::Null;
+            self.env.update(name.to_owned(), var_value.clone());
+            return var_value;
         }
         unreachable!("Expected VarAssignment node!");
     }
 
+    /// Every number is an `f32`, so arithmetic always yields a `Number`: `/`
+    /// keeps the fraction (`7 / 2` is `3.5`), `%` is the truncated remainder
+    /// taking the sign of the dividend and `^` raises with `powf`. `//` starts a
+    /// line comment, floor division is the `floorDiv` native instead, and the
+    /// `mod` native is the floored modulo taking the sign of the divisor.
+    /// `==` never fails: values of different types are unequal, numbers compare
+    /// exactly (so `nan == nan` is false) and arrays and objects compare deeply.
     fn binary_expression(&mut self, node: Rc<ASTNode>) -> RuntimeValue {
         if let ASTNode::BinaryExpression { left, right, operator } = node.as_ref() {
             let left = self.initial_expression(Rc::clone(left));
+            // `??` only evaluates its right side when the left one is null
+            if *operator == "??" {
+                return match left {
+                    RuntimeValue::Null => self.initial_expression(Rc::clone(right)),
+                    left => left
+                };
+            }
+            // `&&` and `||` skip their right side once the left one decides the result
+            if *operator == "&&" || *operator == "||" {
+                let left = self.logical_operand(left, operator);
+                if left == (*operator == "||") {
+                    return RuntimeValue::Bool(left);
+                }
+                let right = self.initial_expression(Rc::clone(right));
+                return RuntimeValue::Bool(self.logical_operand(right, operator));
+            }
             let right = self.initial_expression(Rc::clone(right));
-            return match operator {
-                '-' => left - right,
-                '+' => left + right,
-                '*' => left * right,
-                '/' => left / right,
-                '^' => left.pow(&right),
-                '>' => RuntimeValue::Bool(left > right),
-                '<' => RuntimeValue::Bool(left < right),
-                '=' => RuntimeValue::Bool(left == right),
+            return match *operator {
+                "-" => left - right,
+                "+" => left + right,
+                "*" => {
+                    self.check_repetition(&left, &right);
+                    left * right
+                },
+                "/" => left / right,
+                "%" => left % right,
+                "^" => left.pow(&right),
+                ">" => RuntimeValue::Bool(left > right),
+                "<" => RuntimeValue::Bool(left < right),
+                ">=" => RuntimeValue::Bool(left >= right),
+                "<=" => RuntimeValue::Bool(left <= right),
+                "==" => RuntimeValue::Bool(left == right),
+                "!=" => RuntimeValue::Bool(left != right),
                 _ => unreachable!("Unexpected operator")
             };
         }
         unreachable!("Expected BinaryExpression node!");
     }
 
+    fn logical_operand(&self, value: RuntimeValue, operator: &str) -> bool {
+        match value {
+            RuntimeValue::Bool(value) => value,
+            value if self.loose_conditions => value.is_truthy(),
+            value => panic!("Operator {} expects Bool operands, got {:?}", operator, value.to_type())
+        }
+    }
+
+    fn conditional_expression(&mut self, node: Rc<ASTNode>) -> RuntimeValue {
+        if let ASTNode::ConditionalExpression { condition, when_true, when_false } = node.as_ref() {
+            let condition_value = self.initial_expression(Rc::clone(condition));
+            let branch = if self.expect_bool(condition_value, "ternary") { when_true } else { when_false };
+            return self.initial_expression(Rc::clone(branch));
+        }
+        unreachable!("Expected ConditionalExpression node!")
+    }
+
+    fn check_repetition(&self, left: &RuntimeValue, right: &RuntimeValue) {
+        let (text, count) = match (left, right) {
+            (RuntimeValue::String(text), RuntimeValue::Number(count)) | (RuntimeValue::Number(count), RuntimeValue::String(text)) => (text, *count),
+            _ => return
+        };
+        let length = text.len() as f64 * count.max(0.).floor() as f64;
+        if length > self.max_string_len as f64 {
+            runtime_error(RuntimeError::StringTooLong { length: length as usize, max: self.max_string_len });
+        }
+    }
+
     fn unary_expression(&mut self, node: Rc<ASTNode>) -> RuntimeValue {
         if let ASTNode::UnaryExpression { sign, expr } = node.as_ref() {
             let value = self.initial_expression(Rc::clone(expr));
@@ -108,135 +653,1575 @@ impl Interpreter {
         unreachable!("Expected UnaryExpression node!");
     }
 
+    fn function_declaration(&mut self, node: Rc<ASTNode>) -> RuntimeValue {
+        if let ASTNode::FunctionDeclaration { name, .. } = node.as_ref() {
+            self.functions.insert(name.to_owned(), Rc::clone(&node));
+            return RuntimeValue::Null;
+        }
+        unreachable!("Expected FunctionDeclaration node!")
+    }
+
+    fn return_stmt(&mut self, node: Rc<ASTNode>) -> RuntimeValue {
+        if let ASTNode::ReturnStmt { value } = node.as_ref() {
+            let return_value = match value {
+                Some(value) => self.initial_expression(Rc::clone(value)),
+                None => RuntimeValue::Null
+            };
+            self.flow = Some(Flow::Return(return_value));
+            return RuntimeValue::Null;
+        }
+        unreachable!("Expected ReturnStmt node!")
+    }
+
     fn function_call(&mut self, node: Rc<ASTNode>) -> RuntimeValue {
         if let ASTNode::FunctionCall { name, args } = node.as_ref() {
-            let runtime_values = args.iter().map(|node| self.initial_expression(Rc::clone(node))).collect();
-            return self.fr.call(name, runtime_values);
+            if !self.functions.contains_key(name) && let Some(function) = self.lazy_functions.get(name).cloned() {
+                return function(&mut LazyArgs { interpreter: self, args });
+            }
+            let runtime_values = self.evaluate_args(args);
+            return self.call(name, runtime_values);
         }
         unreachable!("Expected FunctionCall node!")
     }
-    
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum RuntimeValue {
-    Number(f32),
-    String(String),
-    Bool(bool),
-    Null
-}
 
-impl Sub for RuntimeValue {
-    type Output = RuntimeValue;
-    fn sub(self, rhs: Self) -> Self::Output {
-        if let (Self::Number(left_value), Self::Number(right_value)) = (&self, &rhs) {
-            return Self::Number(left_value - right_value);
+    /// Evaluates the arguments of a call, expanding every spread array into
+    /// its elements.
+    fn evaluate_args(&mut self, args: &[Rc<ASTNode>]) -> Vec<RuntimeValue> {
+        let mut runtime_values = vec![];
+        for arg in args {
+            match arg.as_ref() {
+                ASTNode::Spread { expr } => match self.initial_expression(Rc::clone(expr)) {
+                    RuntimeValue::Array(values) => runtime_values.extend(values),
+                    value => panic!("Cannot spread a {:?}, expected an Array", value.to_type())
+                },
+                _ => runtime_values.push(self.initial_expression(Rc::clone(arg)))
+            }
         }
-        panic!("Cannot subtract {:?} and {:?}", self, rhs)
+        runtime_values
     }
-}
 
-impl Add for RuntimeValue {
-    type Output = RuntimeValue;
-    fn add(self, rhs: Self) -> Self::Output {
-        match (&self, &rhs) {
-            (Self::Number(left_value), Self::Number(right_value)) => Self::Number(left_value + right_value),
-            (Self::String(left_value), Self::String(right_value)) => Self::String(format!("{}{}", left_value, right_value)),
-            (Self::Number(left_value), Self::String(right_value)) => Self::String(format!("{}{}", left_value, right_value)),
-            (Self::String(left_value), Self::Number(right_value)) => Self::String(format!("{}{}", left_value, right_value)),
-            _ => panic!("Cannot add {:?} and {:?}", self, rhs)
+    /// Reading a name sees variables first. A name that is only a function
+    /// evaluates to a function value, so `let f = toUpper;` works.
+    fn identifier(&self, name: &str) -> RuntimeValue {
+        if !self.env.is_declared(name) && self.is_function(name) {
+            return RuntimeValue::Function(name.into());
         }
+        self.env.get(name).clone()
     }
-}
 
-impl Mul for RuntimeValue {
-    type Output = RuntimeValue;
-    fn mul(self, rhs: Self) -> Self::Output {
-        match (&self, &rhs) {
-            (Self::Number(left_value), Self::Number(right_value)) => Self::Number(left_value * right_value),
-            (Self::Number(left_value), Self::String(right_value)) => Self::String(right_value.repeat(*left_value as usize)),
-            (Self::String(left_value), Self::Number(right_value)) => Self::String(left_value.repeat(*right_value as usize)),
-            _ => panic!("Cannot multiply {:?} and {:?}", self, rhs)
+    fn is_function(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+            || self.lazy_functions.contains_key(name)
+            || INTRINSICS.iter().any(|(intrinsic, _)| *intrinsic == name)
+            || self.fr.signature(name).is_some()
+    }
+
+    /// Functions and variables live in separate namespaces. A call resolves to
+    /// a function declared with `fn` first and then to a native one, so
+    /// `let print = 1;` leaves `print(x)` working. Only when no function has
+    /// the name does a variable holding a function value get called.
+    fn call(&mut self, name: &str, args: Vec<RuntimeValue>) -> RuntimeValue {
+        if let Some(function) = self.functions.get(name) {
+            return self.call_user_function(Rc::clone(function), args);
+        }
+        if name == "assertThrows" {
+            return self.assert_throws(args);
+        }
+        if name == "dumpEnv" {
+            if !args.is_empty() {
+                panic!("Function dumpEnv expects 0 params, got {}", args.len());
+            }
+            return RuntimeValue::String(self.dump_env().into());
         }
+        if self.fr.signature(name).is_none() && self.env.is_declared(name) {
+            return match self.env.get(name).clone() {
+                RuntimeValue::Function(target) => self.call(&target, args),
+                _ => panic!("{} is a variable, not a function", name)
+            };
+        }
+        if self.lazy_functions.contains_key(name) {
+            panic!("Function {} takes its arguments unevaluated and can only be called by name", name);
+        }
+        self.fr.call(name, args)
     }
-}
 
-impl Div for RuntimeValue {
-    type Output = RuntimeValue;
-    fn div(self, rhs: Self) -> Self::Output {
-        match (&self, &rhs) {
-            (Self::Number(left_value), Self::Number(right_value)) => {
-                if *right_value == 0.0 {
-                    panic!("Cannot divide by zero: {} / {}", left_value, right_value);
-                }
-                Self::Number(left_value / right_value)
+    /// `assertThrows(f)` calls the function `f`, given as a function value or
+    /// by name, without arguments and returns true when it fails, failing
+    /// itself when the call succeeds. Exceeding the step or output limit is
+    /// passed on rather than counted as a failure of `f`.
+    fn assert_throws(&mut self, args: Vec<RuntimeValue>) -> RuntimeValue {
+        let name = match args.as_slice() {
+            [RuntimeValue::String(name) | RuntimeValue::Function(name)] => name.to_string(),
+            _ => panic!("Function assertThrows expects a function or its name, got {:?}", args)
+        };
+        match self.guarded(|interpreter| interpreter.call(&name, vec![])) {
+            Ok(..) => panic!("Assertion failed: {} did not throw", name),
+            // like `try`, hitting a limit set by the host is not a throw
+            Err(error @ (RuntimeError::StepLimitExceeded(..) | RuntimeError::OutputLimitExceeded(..))) => runtime_error(error),
+            Err(..) => {
+                self.thrown.take();
+                RuntimeValue::Bool(true)
             }
-            _ => panic!("Cannot divide {:?} and {:?}", self, rhs)
         }
     }
-}
 
-impl PartialOrd for RuntimeValue {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match (&self, other) {
-            (Self::Number(left_value), Self::Number(right_value)) => {
-                if left_value > right_value {
-                    return Some(Ordering::Greater);
-                } else if left_value < right_value {
-                    return Some(Ordering::Less);
-                }
-                return Some(Ordering::Equal);
+    /// Runs a script-defined function in a new scope whose parent is the global
+    /// scope, so the body sees globals and its params but not the caller's locals.
+    /// Without a `return` the function evaluates to the value of its last statement.
+    fn call_user_function(&mut self, function: Rc<ASTNode>, args: Vec<RuntimeValue>) -> RuntimeValue {
+        if let ASTNode::FunctionDeclaration { name, params, body } = function.as_ref() {
+            let arity = arity(params);
+            if !arity.contains(&args.len()) && arity.start() == arity.end() {
+                panic!("Function {} expects {} params, got {}", name, params.len(), args.len());
+            } else if !arity.contains(&args.len()) {
+                panic!("Function {} expects {} to {} params, got {}", name, arity.start(), arity.end(), args.len());
             }
-            (Self::String(left_value), Self::String(right_value)) => {
-                if left_value > right_value {
-                    return Some(Ordering::Greater);
-                } else if left_value < right_value {
-                    return Some(Ordering::Less);
+            let caller_locals = self.env.detach_locals();
+            let caller_flow = self.flow.take();
+            let result = self.guarded(|interpreter| {
+                interpreter.env.push_scope();
+                let mut args = args.into_iter();
+                for param in params {
+                    let value = match (args.next(), &param.default) {
+                        (Some(arg), _) => arg,
+                        (None, Some(default)) => interpreter.initial_expression(Rc::clone(default)),
+                        (None, None) => unreachable!("Arity allows leaving out only params with a default")
+                    };
+                    interpreter.bind_pattern(&param.pattern, value);
+                }
+                let last_value = interpreter.execute_block(body);
+                interpreter.env.pop_scope();
+                match interpreter.flow.take() {
+                    Some(Flow::Return(value)) => value,
+                    _ => last_value
+                }
+            });
+            self.env.restore_locals(caller_locals);
+            self.flow = caller_flow;
+            return result.unwrap_or_else(|error| runtime_error(error));
+        }
+        unreachable!("Expected FunctionDeclaration node!")
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern, value: RuntimeValue) {
+        match (pattern, value) {
+            (Pattern::Name(name), value) => self.env.add(name.to_owned(), value),
+            (Pattern::Ignore, _) => {},
+            (Pattern::Array(patterns), RuntimeValue::Array(values)) if patterns.len() == values.len() => {
+                for (pattern, value) in patterns.iter().zip(values) {
+                    self.bind_pattern(pattern, value);
                 }
-                return Some(Ordering::Equal);
             },
-            _ => panic!("Cannot compare {:?} and {:?}", self.to_type(), other.to_type())
+            (Pattern::Array(patterns), value) => {
+                panic!("Cannot destructure {} into a pattern of {} elements", value, patterns.len())
+            }
         }
     }
-}
 
-impl RuntimeValue {
-    fn pow(&self, rhs: &Self) -> Self {
-        match (self, rhs) {
-            (Self::Number(left_value), Self::Number(right_value)) => {
-                Self::Number(left_value.powf(*right_value))
+    fn method_call(&mut self, node: Rc<ASTNode>) -> RuntimeValue {
+        if let ASTNode::MethodCall { receiver, name, args } = node.as_ref() {
+            let receiver = self.initial_expression(Rc::clone(receiver));
+            let is_known_method = match receiver {
+                RuntimeValue::String(..) => STRING_METHODS.contains(&name.as_str()),
+                _ => false
+            };
+            if !is_known_method {
+                panic!("{:?} has no method named {}", receiver.to_type(), name);
             }
-            _ => panic!("Cannot rise {:?} to the power of {:?}", self, rhs)
+            let mut runtime_values = vec![receiver];
+            runtime_values.extend(self.evaluate_args(args));
+            return self.fr.call(name, runtime_values);
         }
+        unreachable!("Expected MethodCall node!")
     }
+    
 }
 
-impl RuntimeValue {
-    pub fn to_type(&self) -> RuntimeType {
-        match self {
-            Self::Bool(..) => RuntimeType::Bool,
-            Self::Number(..) => RuntimeType::Number,
-            Self::String(..) => RuntimeType::String,
-            Self::Null => RuntimeType::Null,
+/// The element of an array or the character of a string at `index`, which
+/// counts from the end when negative.
+fn element_at(target: RuntimeValue, index: RuntimeValue) -> RuntimeValue {
+    let index = match index {
+        RuntimeValue::Number(index) if index.fract() == 0.0 => index as isize,
+        RuntimeValue::Number(index) => panic!("Index must be a whole number, got {}", index),
+        index => panic!("Index must be a Number, got {:?}", index.to_type())
+    };
+    let resolve = |len: usize| {
+        let resolved = if index < 0 { len as isize + index } else { index };
+        if resolved < 0 || resolved >= len as isize {
+            panic!("Index {} is out of bounds for length {}", index, len);
         }
+        resolved as usize
+    };
+    match target {
+        RuntimeValue::Array(mut values) => {
+            let i = resolve(values.len());
+            values.swap_remove(i)
+        },
+        RuntimeValue::String(value) => {
+            let chars: Vec<char> = value.chars().collect();
+            RuntimeValue::String(chars[resolve(chars.len())].to_string().into())
+        },
+        target => panic!("Cannot index a {:?}, expected an Array or a String", target.to_type())
     }
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+/// The unevaluated arguments of a call to a lazy function.
+pub struct LazyArgs<'a> {
+    interpreter: &'a mut Interpreter,
+    args: &'a [Rc<ASTNode>]
+}
 
-    #[test]
-    fn i_test_var_dec() {
-        let mut i = Interpreter::new();
-        let output = i.run(r#"
-            let x = 10 + 5;
-            let y = x * 2;
-            y = 8;
-            print("The result is " + y);
-            "#);
-        // dbg!(&i.env);
-        dbg!(&output);
-        dbg!(&i.env);
+impl LazyArgs<'_> {
+    pub fn len(&self) -> usize {
+        self.args.len()
     }
-}
\ No newline at end of file
+
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    pub fn node(&self, index: usize) -> &Rc<ASTNode> {
+        self.args.get(index).unwrap_or_else(|| panic!("Missing argument at position {}", index))
+    }
+
+    /// Evaluates the argument at `index` in the caller's scope. Each call
+    /// evaluates it again.
+    pub fn eval(&mut self, index: usize) -> RuntimeValue {
+        let node = Rc::clone(self.node(index));
+        self.interpreter.initial_expression(node)
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeValue {
+    Number(f32),
+    String(Rc<str>),
+    Bool(bool),
+    Array(Vec<RuntimeValue>),
+    Object(ObjectMap),
+    /// A function referenced by name, resolved when it is called.
+    Function(Rc<str>),
+    Null
+}
+
+impl Display for RuntimeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Function(name) => write!(f, "<fn {}>", name),
+            Self::Bool(value) => write!(f, "{}", value),
+            Self::Number(value) => match f.precision() {
+                Some(precision) => write!(f, "{}", round_for_display(*value, precision)),
+                None => write!(f, "{}", value)
+            },
+            Self::String(value) => write!(f, "{}", value),
+            Self::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    value.fmt_nested(f)?;
+                }
+                write!(f, "]")
+            },
+            Self::Object(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{:?}: ", key)?;
+                    value.fmt_nested(f)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl RuntimeValue {
+    /// Writes a value nested inside an array or object, quoting strings. The
+    /// formatter is passed on so a precision also applies to nested numbers.
+    fn fmt_nested(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(value) => write!(f, "{:?}", value),
+            value => Display::fmt(value, f)
+        }
+    }
+
+    /// Renders the value as compact JSON. Non-finite numbers and functions have
+    /// no JSON form and become `null`.
+    pub fn to_json(&self) -> String {
+        match self {
+            Self::Null | Self::Function(..) => "null".to_string(),
+            Self::Bool(value) => value.to_string(),
+            Self::Number(value) if value.is_finite() => value.to_string(),
+            Self::Number(..) => "null".to_string(),
+            Self::String(value) => json_string(value),
+            Self::Array(values) => {
+                format!("[{}]", values.iter().map(|value| value.to_json()).collect::<Vec<String>>().join(","))
+            },
+            Self::Object(entries) => {
+                let members: Vec<String> = entries.iter()
+                    .map(|(key, value)| format!("{}:{}", json_string(key), value.to_json()))
+                    .collect();
+                format!("{{{}}}", members.join(","))
+            }
+        }
+    }
+}
+
+/// Rounds `value` to `precision` decimals, dropping trailing zeros, so with a
+/// precision of 2 `0.1 + 0.2` displays as `0.3`.
+fn round_for_display(value: f32, precision: usize) -> String {
+    let mut rounded = format!("{:.*}", precision, value);
+    if rounded.contains('.') {
+        rounded = rounded.trim_end_matches('0').trim_end_matches('.').to_string();
+    }
+    if rounded == "-0" {
+        rounded = "0".to_string();
+    }
+    rounded
+}
+
+/// The entries of an object, kept in insertion order so objects always
+/// print and iterate the same way. Setting a key that already exists keeps
+/// its original position. Two objects are equal when they hold the same
+/// entries, whatever their order.
+#[derive(Debug, Clone, Default)]
+pub struct ObjectMap {
+    entries: Vec<(String, RuntimeValue)>
+}
+
+impl ObjectMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&RuntimeValue> {
+        self.entries.iter().find(|(entry_key, _)| entry_key == key).map(|(_, value)| value)
+    }
+
+    /// Sets `key` to `value`, returning the value it replaced.
+    pub fn insert(&mut self, key: String, value: RuntimeValue) -> Option<RuntimeValue> {
+        match self.entries.iter_mut().find(|(entry_key, _)| *entry_key == key) {
+            Some((_, entry_value)) => Some(std::mem::replace(entry_value, value)),
+            None => {
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &RuntimeValue)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+impl PartialEq for ObjectMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl FromIterator<(String, RuntimeValue)> for ObjectMap {
+    fn from_iter<T: IntoIterator<Item = (String, RuntimeValue)>>(iter: T) -> Self {
+        let mut object = Self::new();
+        for (key, value) in iter {
+            object.insert(key, value);
+        }
+        object
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::from("\"");
+    for char in value.chars() {
+        match char {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            char if char.is_control() => json.push_str(&format!("\\u{:04x}", char as u32)),
+            char => json.push(char)
+        }
+    }
+    json.push('"');
+    json
+}
+
+impl Sub for RuntimeValue {
+    type Output = RuntimeValue;
+    fn sub(self, rhs: Self) -> Self::Output {
+        if let (Self::Number(left_value), Self::Number(right_value)) = (&self, &rhs) {
+            return Self::Number(left_value - right_value);
+        }
+        panic!("Cannot subtract {:?} and {:?}", self, rhs)
+    }
+}
+
+impl Add for RuntimeValue {
+    type Output = RuntimeValue;
+    fn add(self, rhs: Self) -> Self::Output {
+        match (&self, &rhs) {
+            (Self::Number(left_value), Self::Number(right_value)) => Self::Number(left_value + right_value),
+            (Self::String(left_value), Self::String(right_value)) => Self::String(format!("{}{}", left_value, right_value).into()),
+            (Self::Number(left_value), Self::String(right_value)) => Self::String(format!("{}{}", left_value, right_value).into()),
+            (Self::String(left_value), Self::Number(right_value)) => Self::String(format!("{}{}", left_value, right_value).into()),
+            _ => panic!("Cannot add {:?} and {:?}", self, rhs)
+        }
+    }
+}
+
+impl Mul for RuntimeValue {
+    type Output = RuntimeValue;
+    fn mul(self, rhs: Self) -> Self::Output {
+        match (&self, &rhs) {
+            (Self::Number(left_value), Self::Number(right_value)) => Self::Number(left_value * right_value),
+            (Self::Number(left_value), Self::String(right_value)) => Self::String(right_value.repeat(*left_value as usize).into()),
+            (Self::String(left_value), Self::Number(right_value)) => Self::String(left_value.repeat(*right_value as usize).into()),
+            _ => panic!("Cannot multiply {:?} and {:?}", self, rhs)
+        }
+    }
+}
+
+impl Div for RuntimeValue {
+    type Output = RuntimeValue;
+    fn div(self, rhs: Self) -> Self::Output {
+        match (&self, &rhs) {
+            (Self::Number(left_value), Self::Number(right_value)) => {
+                if *right_value == 0.0 {
+                    panic!("Cannot divide by zero: {} / {}", left_value, right_value);
+                }
+                Self::Number(left_value / right_value)
+            }
+            _ => panic!("Cannot divide {:?} and {:?}", self, rhs)
+        }
+    }
+}
+
+impl Rem for RuntimeValue {
+    type Output = RuntimeValue;
+    fn rem(self, rhs: Self) -> Self::Output {
+        match (&self, &rhs) {
+            (Self::Number(left_value), Self::Number(right_value)) => {
+                if *right_value == 0.0 {
+                    panic!("Cannot divide by zero: {} % {}", left_value, right_value);
+                }
+                Self::Number(left_value % right_value)
+            }
+            _ => panic!("Cannot take the remainder of {:?} and {:?}", self, rhs)
+        }
+    }
+}
+
+/// Ordering used by `>`, `<`, `>=` and `<=`: numbers compare by value (any
+/// comparison with `nan` is false), strings compare lexicographically by
+/// byte and any other pairing is an error rather than a silent `false`.
+impl PartialOrd for RuntimeValue {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (&self, other) {
+            (Self::Number(left_value), Self::Number(right_value)) => left_value.partial_cmp(right_value),
+            (Self::String(left_value), Self::String(right_value)) => Some(left_value.as_ref().cmp(right_value.as_ref())),
+            // null has no order, it can only be tested with == and !=
+            (Self::Null, _) | (_, Self::Null) => {
+                panic!("Cannot order {:?} and {:?}, use == or != to compare with null", self.to_type(), other.to_type())
+            },
+            _ => panic!("Cannot compare {:?} and {:?}", self.to_type(), other.to_type())
+        }
+    }
+}
+
+impl RuntimeValue {
+    fn pow(&self, rhs: &Self) -> Self {
+        match (self, rhs) {
+            (Self::Number(left_value), Self::Number(right_value)) => {
+                Self::Number(left_value.powf(*right_value))
+            }
+            _ => panic!("Cannot rise {:?} to the power of {:?}", self, rhs)
+        }
+    }
+}
+
+impl RuntimeValue {
+    pub fn to_type(&self) -> RuntimeType {
+        match self {
+            Self::Bool(..) => RuntimeType::Bool,
+            Self::Number(..) => RuntimeType::Number,
+            Self::String(..) => RuntimeType::String,
+            Self::Array(..) => RuntimeType::Array,
+            Self::Object(..) => RuntimeType::Object,
+            Self::Function(..) => RuntimeType::Function,
+            Self::Null => RuntimeType::Null,
+        }
+    }
+
+    /// The lowercase name scripts see for the type of the value.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Bool(..) => "bool",
+            Self::Number(..) => "number",
+            Self::String(..) => "string",
+            Self::Array(..) => "array",
+            Self::Object(..) => "object",
+            Self::Function(..) => "function",
+            Self::Null => "null",
+        }
+    }
+
+    /// Zero, NaN, the empty string and `null` are falsy; every other value is truthy.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Self::Bool(value) => *value,
+            Self::Number(value) => *value != 0. && !value.is_nan(),
+            Self::String(value) => !value.is_empty(),
+            Self::Array(..) | Self::Object(..) | Self::Function(..) => true,
+            Self::Null => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lang::func::{function_registry::ParamCount, native_state::SharedBuffer};
+
+    use super::*;
+
+    #[test]
+    fn i_test_var_dec() {
+        let mut i = Interpreter::new();
+        let output = i.run(r#"
+            let x = 10 + 5;
+            let y = x * 2;
+            y = 8;
+            print("The result is " + y);
+            "#);
+        // dbg!(&i.env);
+        dbg!(&output);
+        dbg!(&i.env);
+    }
+
+    #[test]
+    fn i_test_string_method_chain() {
+        let mut i = Interpreter::new();
+        let output = i.run(r#"
+            let s = "  Hello  ".trim().toLower();
+            s;
+            "#);
+        assert_eq!(output, RuntimeValue::String("hello".into()));
+        let output = i.run(r#"
+            let t = " abc ".toUpper().trim().substring(1, 2);
+            t;
+            "#);
+        assert_eq!(output, RuntimeValue::String("BC".into()));
+    }
+
+    #[test]
+    fn i_test_typed_eval() {
+        let mut i = Interpreter::new();
+        i.run("let base = 10;");
+        assert_eq!(i.eval_number("base * 2 + 1"), Ok(21.));
+        assert_eq!(i.eval_string(r#""v" + base"#), Ok("v10".to_string()));
+        assert_eq!(i.eval_bool("base > 3;"), Ok(true));
+    }
+
+    #[test]
+    fn i_test_typed_eval_errors() {
+        let mut i = Interpreter::new();
+        assert_eq!(
+            i.eval_number(r#""ten""#),
+            Err(RuntimeError::TypeMismatch { expected: RuntimeType::Number, found: RuntimeType::String })
+        );
+        assert!(matches!(i.eval_bool(r#""a" - 1"#), Err(RuntimeError::Message(message)) if message.contains("Cannot subtract")));
+    }
+
+    #[test]
+    fn i_test_function_hoisting() {
+        let mut i = Interpreter::new();
+        let output = i.run(r#"
+            let result = 0;
+            main();
+            fn main() {
+                helper(20);
+            }
+            fn helper(value) {
+                result = value + 1;
+            }
+            result;
+            "#);
+        assert_eq!(output, RuntimeValue::Number(21.));
+    }
+
+    #[test]
+    #[should_panic]
+    fn i_test_let_is_not_hoisted() {
+        let mut i = Interpreter::new();
+        i.run(r#"
+            print(x);
+            let x = 1;
+            "#);
+    }
+
+    #[test]
+    fn i_test_array_literal() {
+        let mut i = Interpreter::new();
+        let output = i.run("let a = [1, 2 + 1, \"x\", [true],]; a;");
+        assert_eq!(output, RuntimeValue::Array(vec![
+            RuntimeValue::Number(1.),
+            RuntimeValue::Number(3.),
+            RuntimeValue::String("x".into()),
+            RuntimeValue::Array(vec![RuntimeValue::Bool(true)])
+        ]));
+        assert_eq!(output.to_string(), r#"[1, 3, "x", [true]]"#);
+        assert_eq!(i.run("let b = []; b;"), RuntimeValue::Array(vec![]));
+    }
+
+    #[test]
+    fn i_test_index() {
+        let output = SharedBuffer::new();
+        let mut i = Interpreter::with_options(InterpreterOptions { output: Some(Box::new(output.clone())), ..Default::default() });
+        i.run("let a = [10,20,30]; print(a[1]);");
+        assert_eq!(output.contents(), "20\n");
+        assert_eq!(i.run("a[0] + a[2];"), RuntimeValue::Number(40.));
+        assert_eq!(i.run("a[-1];"), RuntimeValue::Number(30.));
+        assert_eq!(i.run("a[len(a) - 3];"), RuntimeValue::Number(10.));
+        assert_eq!(i.run("[[1, 2], [3, 4]][1][0];"), RuntimeValue::Number(3.));
+        assert_eq!(i.run("\"héllo\"[1];"), RuntimeValue::String("é".into()));
+        assert_eq!(i.run("\"abc\".toUpper()[-1];"), RuntimeValue::String("C".into()));
+        assert_eq!(i.run("-a[0] ^ 2;"), RuntimeValue::Number(100.));
+
+        assert_eq!(i.try_run("a[3];"), Err("Index 3 is out of bounds for length 3".to_string()));
+        assert_eq!(i.try_run("a[-4];"), Err("Index -4 is out of bounds for length 3".to_string()));
+        assert_eq!(i.try_run("[][0];"), Err("Index 0 is out of bounds for length 0".to_string()));
+        assert_eq!(i.try_run("a[0.5];"), Err("Index must be a whole number, got 0.5".to_string()));
+        assert_eq!(i.try_run("a[\"0\"];"), Err("Index must be a Number, got String".to_string()));
+        assert_eq!(i.try_run("let n = 5; n[0];"), Err("Cannot index a Number, expected an Array or a String".to_string()));
+    }
+
+    #[test]
+    fn i_test_push_pop_through_variables() {
+        let mut i = Interpreter::new();
+        i.run("let a = [1, 2]; let b = push(a, 3);");
+        assert_eq!(i.run("a;"), RuntimeValue::Array(vec![RuntimeValue::Number(1.), RuntimeValue::Number(2.)]));
+        i.run("a = push(a, 3); a = push(a, \"four\");");
+        assert_eq!(i.run("len(a);"), RuntimeValue::Number(4.));
+        assert_eq!(i.run("a[3];"), RuntimeValue::String("four".into()));
+        assert_eq!(i.run("a == push(b, \"four\");"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("pop(a);"), RuntimeValue::String("four".into()));
+        assert_eq!(i.run("len(a);"), RuntimeValue::Number(4.));
+        assert_eq!(i.run("pop([]);"), RuntimeValue::Null);
+    }
+
+    #[test]
+    fn i_test_clone() {
+        let mut i = Interpreter::new();
+        i.run("let a = [1, [2, \"x\"], { k: 3 }]; let b = clone(a); let c = a;");
+        assert_eq!(i.run("a == b;"), RuntimeValue::Bool(true));
+        // values are copied on assignment, so clone matches a plain `let`
+        assert_eq!(i.run("b == c;"), RuntimeValue::Bool(true));
+        i.run("b = push(b, 4); a = push(a, 5);");
+        assert_eq!(i.run("a;").to_string(), r#"[1, [2, "x"], {"k": 3}, 5]"#);
+        assert_eq!(i.run("b;").to_string(), r#"[1, [2, "x"], {"k": 3}, 4]"#);
+        assert_eq!(i.run("c;").to_string(), r#"[1, [2, "x"], {"k": 3}]"#);
+        assert_eq!(i.run("clone(\"text\");"), RuntimeValue::String("text".into()));
+        assert_eq!(i.run("clone(null);"), RuntimeValue::Null);
+    }
+
+    #[test]
+    fn i_test_fill_too_large_is_catchable() {
+        let mut i = Interpreter::new();
+        assert!(i.try_run("fill(0, 1e12);").unwrap_err().starts_with("Function fill cannot build an array of 1000000000000 elements"));
+        assert_eq!(i.run("try { fill(0, 1e12); } catch (e) { \"caught\"; }"), RuntimeValue::String("caught".into()));
+    }
+
+    #[test]
+    fn i_test_print_array_literals() {
+        let output = SharedBuffer::new();
+        let mut i = Interpreter::with_options(InterpreterOptions { output: Some(Box::new(output.clone())), ..Default::default() });
+        i.run("print([1, 2, 3]); print([]); print([[], [1,],]);");
+        assert_eq!(output.contents(), "[1, 2, 3]\n[]\n[[], [1]]\n");
+        assert_eq!(i.run("[1, 2, 3];").to_type(), RuntimeType::Array);
+        assert_eq!(i.run("len([1, 2, 3]);"), RuntimeValue::Number(3.));
+    }
+
+    #[test]
+    fn i_test_options() {
+        let options = InterpreterOptions { step_limit: Some(4), random_seed: Some(7), ..Default::default() };
+        let mut i = Interpreter::with_options(options);
+        assert_eq!(i.eval_number("1 + 2"), Ok(3.));
+        assert_eq!(i.eval_number("1 + 2 + 3"), Err(RuntimeError::StepLimitExceeded(4)));
+
+        let mut seeded = Interpreter::with_options(InterpreterOptions { random_seed: Some(7), ..Default::default() });
+        assert_eq!(i.eval_number("random()"), seeded.eval_number("random()"));
+    }
+
+    #[test]
+    fn i_test_output_option() {
+        let output = SharedBuffer::new();
+        let mut i = Interpreter::with_options(InterpreterOptions { output: Some(Box::new(output.clone())), ..Default::default() });
+        i.run(r#"print("hello"); print([1, "a"]);"#);
+        assert_eq!(output.contents(), "hello\n[1, \"a\"]\n");
+    }
+
+    #[test]
+    fn i_test_max_output_bytes() {
+        let output = SharedBuffer::new();
+        let mut i = Interpreter::with_options(InterpreterOptions { output: Some(Box::new(output.clone())), ..Default::default() });
+        i.set_max_output_bytes(Some(20));
+        i.run(r#"print("small"); print(12);"#);
+        assert_eq!(output.contents(), "small\n12\n");
+
+        let err = catch(|| i.run(r#"while (true) { print("spam"); }"#)).unwrap_err();
+        assert_eq!(err, RuntimeError::OutputLimitExceeded(20));
+        assert_eq!(output.contents(), "small\n12\nspam\nspam\n");
+        let err = catch(|| i.run(r#"try { print("more"); } catch (e) { 1; }"#)).unwrap_err();
+        assert_eq!(err, RuntimeError::OutputLimitExceeded(20));
+
+        i.set_max_output_bytes(None);
+        i.run(r#"print("free");"#);
+        assert!(output.contents().ends_with("spam\nfree\n"));
+    }
+
+    #[test]
+    fn i_test_print_structured_values() {
+        let output = SharedBuffer::new();
+        let mut i = Interpreter::with_options(InterpreterOptions { output: Some(Box::new(output.clone())), ..Default::default() });
+        i.run(r#"
+            let point = { y: 2, x: 1.5, "label": 'a "b"' };
+            let list = [1, "two", [true, toNumber("none")], { k: nan }];
+            print(point);
+            printJson(point);
+            print(list);
+            printJson(list);
+        "#);
+        assert_eq!(output.contents(), [
+            r#"{"y": 2, "x": 1.5, "label": "a \"b\""}"#,
+            r#"{"y":2,"x":1.5,"label":"a \"b\""}"#,
+            r#"[1, "two", [true, null], {"k": NaN}]"#,
+            r#"[1,"two",[true,null],{"k":null}]"#,
+            ""
+        ].join("\n"));
+        assert_eq!(RuntimeValue::String("tab\there\u{1}".into()).to_json(), r#""tab\there\u0001""#);
+    }
+
+    #[test]
+    fn i_test_error_output_option() {
+        let output = SharedBuffer::new();
+        let error_output = SharedBuffer::new();
+        let mut i = Interpreter::with_options(InterpreterOptions {
+            output: Some(Box::new(output.clone())),
+            error_output: Some(Box::new(error_output.clone())),
+            ..Default::default()
+        });
+        i.run(r#"print("ok"); printErr("oops"); eprint(2);"#);
+        assert_eq!(output.contents(), "ok\n");
+        assert_eq!(error_output.contents(), "oops\n2\n");
+    }
+
+    #[test]
+    fn i_test_redeclaration() {
+        let mut i = Interpreter::new();
+        let result = catch(|| i.run("let x = 1; let x = 2;"));
+        assert_eq!(result, Err(RuntimeError::AlreadyDeclared("x".to_string())));
+
+        let mut i = Interpreter::new();
+        let result = catch(|| i.run("let x = 1; fn f() { let x = 2; } f();"));
+        assert_eq!(result, Err(RuntimeError::AlreadyDeclared("x".to_string())));
+    }
+
+    #[test]
+    fn i_test_allow_shadowing() {
+        let output = SharedBuffer::new();
+        let mut i = Interpreter::with_options(InterpreterOptions {
+            allow_shadowing: true,
+            output: Some(Box::new(output.clone())),
+            ..Default::default()
+        });
+        let result = i.run("let x = 1; fn f() { let x = 2; print(x); } f(); x;");
+        assert_eq!(result, RuntimeValue::Number(1.));
+        assert_eq!(output.contents(), "2\n");
+
+        let result = catch(|| i.run("fn g() { let y = 1; let y = 2; } g();"));
+        assert_eq!(result, Err(RuntimeError::AlreadyDeclared("y".to_string())));
+    }
+
+    #[test]
+    fn i_test_special_number_literals() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.eval_bool("inf > 1e30"), Ok(true));
+        assert_eq!(i.eval_number("-inf"), Ok(f32::NEG_INFINITY));
+        assert_eq!(i.eval_number("2.5E-1 + 1e+1"), Ok(10.25));
+        assert_eq!(i.eval_bool("isFinite(nan)"), Ok(false));
+        assert_eq!(i.eval_bool("isFinite(1e3)"), Ok(true));
+        assert_eq!(i.eval_bool("nan > 1"), Ok(false));
+        assert_eq!(i.eval_bool("nan < 1"), Ok(false));
+    }
+
+    #[test]
+    fn i_test_reset() {
+        let mut i = Interpreter::new();
+        i.add_function(Function::new(
+            "double".to_string(),
+            ParamCount::Fixed(1),
+            vec![RuntimeType::Number],
+            RuntimeType::Number,
+            Box::new(|args| RuntimeValue::Number(args.as_f32(0) * 2.))
+        ));
+        i.run("let x = 5; fn triple(n) { x = n * 3; }");
+        i.reset();
+        assert!(catch(|| i.run("x;")).is_err());
+        assert!(catch(|| i.run("triple(1);")).is_err());
+        assert_eq!(i.eval_number("double(4)"), Ok(8.));
+        assert_eq!(i.run("let x = 1; x;"), RuntimeValue::Number(1.));
+    }
+
+    #[test]
+    fn i_test_check() {
+        let output = SharedBuffer::new();
+        let mut i = Interpreter::with_options(InterpreterOptions { output: Some(Box::new(output.clone())), ..Default::default() });
+        i.run("fn known(a) { print(a); }");
+        let diagnostics = i.check(r#"
+            print("not printed");
+            let x = missing(1);
+            substring("abc", 1);
+            known(1, 2);
+            later(1);
+            let y = "a" - 1;
+            toNumber(5);
+            fn later(b) { print(b); }
+            "#);
+        let messages: Vec<String> = diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect();
+        assert_eq!(messages, vec![
+            "Function missing does not exist",
+            "Function substring expects 3 params, got 2",
+            "Function known expects 1 params, got 2",
+            "Operator - cannot be applied to String and Number",
+            "Param 0 of function toNumber expected String, got Number",
+        ]);
+        assert_eq!(output.contents(), "");
+        assert_eq!(i.check("let x = ;").len(), 1);
+    }
+
+    #[test]
+    fn i_test_return() {
+        let mut i = Interpreter::new();
+        let output = i.run(r#"
+            fn sign(n) {
+                if (n < 0) {
+                    return -1;
+                }
+                if (n > 0) {
+                    return 1;
+                }
+                return 0;
+            }
+            let result = sign(-5) + sign(3) * 10 + sign(0) * 100;
+            result;
+            "#);
+        assert_eq!(output, RuntimeValue::Number(9.));
+    }
+
+    #[test]
+    fn i_test_call_function() {
+        let mut i = Interpreter::new();
+        i.run("fn square(x) { return x * x; }");
+        for (arg, expected) in [(2., 4.), (3., 9.), (-4., 16.)] {
+            assert_eq!(i.call_function("square", vec![RuntimeValue::Number(arg)]), Ok(RuntimeValue::Number(expected)));
+        }
+        assert_eq!(
+            i.call_function("toUpper", vec![RuntimeValue::String("abc".into())]),
+            Ok(RuntimeValue::String("ABC".into()))
+        );
+        assert!(i.call_function("square", vec![RuntimeValue::String("x".into())]).is_err());
+        assert!(i.call_function("missing", vec![]).is_err());
+    }
+
+    #[test]
+    fn i_test_error_inside_function_restores_scopes() {
+        let mut i = Interpreter::new();
+        i.run(r#"fn fail(x) { let local = 1; return x - "a"; }"#);
+        assert!(i.call_function("fail", vec![RuntimeValue::Number(1.)]).is_err());
+        assert!(catch(|| i.run("local;")).is_err());
+        assert_eq!(i.run("let local = 2; local;"), RuntimeValue::Number(2.));
+    }
+
+    #[test]
+    fn i_test_strings_are_shared() {
+        let big: Rc<str> = "x".repeat(1 << 20).into();
+        let value = RuntimeValue::String(Rc::clone(&big));
+        let copy = value.clone();
+        match (&value, &copy) {
+            (RuntimeValue::String(left), RuntimeValue::String(right)) => assert!(Rc::ptr_eq(left, right)),
+            _ => unreachable!()
+        }
+        assert_eq!(Rc::strong_count(&big), 3);
+
+        let mut i = Interpreter::new();
+        i.run(r#"let s = "shared literal";"#);
+        match (i.run("s;"), i.run("s;")) {
+            (RuntimeValue::String(left), RuntimeValue::String(right)) => assert!(Rc::ptr_eq(&left, &right)),
+            values => panic!("Expected two strings, got {:?}", values)
+        }
+    }
+
+    #[test]
+    fn i_test_expression_statement() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.run("(1 + 2) * 3;"), RuntimeValue::Number(9.));
+        assert_eq!(i.run("fn one() { return 1; } one() + 1;"), RuntimeValue::Number(2.));
+    }
+
+    #[test]
+    fn i_test_paren_statements() {
+        let mut i = Interpreter::new();
+        i.run("let a = 2; let b = 3; fn f(x) { return x * 10; }");
+        assert_eq!(i.run("(a + b);"), RuntimeValue::Number(5.));
+        assert_eq!(i.run("(f(a));"), RuntimeValue::Number(20.));
+        assert_eq!(i.run("((a + (b * 2)) * (f(1)));"), RuntimeValue::Number(80.));
+    }
+
+    #[test]
+    fn i_test_string_comparison() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.run(r#""apple" < "banana";"#), RuntimeValue::Bool(true));
+        assert_eq!(i.run(r#""apple" > "banana";"#), RuntimeValue::Bool(false));
+        assert_eq!(i.run(r#""Zebra" < "apple";"#), RuntimeValue::Bool(true));
+        assert_eq!(i.run(r#""pear" >= "pear";"#), RuntimeValue::Bool(true));
+        assert_eq!(i.run(r#""pea" <= "pear";"#), RuntimeValue::Bool(true));
+        assert_eq!(i.run("3 >= 4;"), RuntimeValue::Bool(false));
+        assert_eq!(i.run("4 <= 4;"), RuntimeValue::Bool(true));
+
+        let err = catch(|| i.run(r#""apple" < 1;"#)).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot compare String and Number");
+    }
+
+    #[test]
+    fn i_test_loose_conditions() {
+        let mut i = Interpreter::new();
+        let err = catch(|| i.run("if 1 { 2; }")).unwrap_err();
+        assert_eq!(err.to_string(), "Expression inside if must return a bool value");
+
+        i.set_loose_conditions(true);
+        assert_eq!(i.run("if 1 { 2; }"), RuntimeValue::Number(2.));
+        assert_eq!(i.run(r#"if "" { 2; } else { 3; }"#), RuntimeValue::Number(3.));
+        assert_eq!(i.run("let n = 0; if n { 2; } else { 3; }"), RuntimeValue::Number(3.));
+        assert_eq!(i.run("if (toBool(\"x\")) { 4; }"), RuntimeValue::Number(4.));
+        assert_eq!(i.run("let left = 3; while (left) { left = left - 1; } left;"), RuntimeValue::Number(0.));
+        assert_eq!(i.run("let runs = 0; for (let k = 2; k; k = k - 1) { runs = runs + 1; } runs;"), RuntimeValue::Number(2.));
+        assert_eq!(i.run("let once = 0; do { once = once + 1; } while (null); once;"), RuntimeValue::Number(1.));
+        assert_eq!(i.run(r#""" ? 1 : 2;"#), RuntimeValue::Number(2.));
+        assert_eq!(i.run(r#"1 && "x";"#), RuntimeValue::Bool(true));
+        assert_eq!(i.run("0 || null;"), RuntimeValue::Bool(false));
+    }
+
+    #[test]
+    fn i_test_do_while() {
+        let mut i = Interpreter::new();
+        i.run("let runs = 0; do { runs = runs + 1; } while (false);");
+        assert_eq!(i.run("runs;"), RuntimeValue::Number(1.));
+
+        i.run("let n = 0; do { let next = n + 1; n = next; } while (n < 5);");
+        assert_eq!(i.run("n;"), RuntimeValue::Number(5.));
+
+        let err = catch(|| i.run("do { 1; } while (1);")).unwrap_err();
+        assert_eq!(err.to_string(), "Expression inside do-while must return a bool value");
+    }
+
+    #[test]
+    fn i_test_pattern_params() {
+        let mut i = Interpreter::new();
+        i.run("fn dist([x, y]) { return x * x + y * y; } fn first([[a, b], c]) { return a; }");
+        assert_eq!(i.run("dist([3, 4]);"), RuntimeValue::Number(25.));
+        assert_eq!(i.run(r#"first([["a", "b"], "c"]);"#), RuntimeValue::String("a".into()));
+
+        let err = catch(|| i.run("dist([1, 2, 3]);")).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot destructure [1, 2, 3] into a pattern of 2 elements");
+        assert!(catch(|| i.run("dist(5);")).is_err());
+        assert!(catch(|| i.run("x;")).is_err());
+    }
+
+    #[test]
+    fn i_test_array_constructors() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.run("array(1, 2, 3);"), i.run("[1, 2, 3];"));
+        assert_eq!(i.run(r#"fill("x", 2);"#).to_string(), r#"["x", "x"]"#);
+    }
+
+    #[test]
+    fn i_test_spread_args() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.run("max(...[1, 7, 3]);"), RuntimeValue::Number(7.));
+        assert_eq!(i.run("let rest = [4, 9]; max(2, ...rest, 5);"), RuntimeValue::Number(9.));
+        assert_eq!(i.run("fn add(a, b) { return a + b; } add(...[1, 2]);"), RuntimeValue::Number(3.));
+        assert_eq!(i.run(r#""abcd".substring(...[1, 2]);"#), RuntimeValue::String("bc".into()));
+
+        let err = catch(|| i.run("max(...4);")).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot spread a Number, expected an Array");
+        assert!(catch(|| i.run("max(...[]);")).is_err());
+    }
+
+    #[test]
+    fn i_test_default_params() {
+        let mut i = Interpreter::new();
+        i.run(r#"fn greet(name, greeting = "Hello") { return greeting + ", " + name; }"#);
+        assert_eq!(i.run(r#"greet("Sam");"#), RuntimeValue::String("Hello, Sam".into()));
+        assert_eq!(i.run(r#"greet("Sam", "Hi");"#), RuntimeValue::String("Hi, Sam".into()));
+
+        i.run("fn scale(x, factor = x * 2) { return x * factor; }");
+        assert_eq!(i.run("scale(3);"), RuntimeValue::Number(18.));
+
+        let err = catch(|| i.run("greet();")).unwrap_err();
+        assert_eq!(err.to_string(), "Function greet expects 1 to 2 params, got 0");
+        assert_eq!(i.check("greet(1, 2, 3);")[0].message, "Function greet expects 1 to 2 params, got 3");
+        assert!(catch(|| i.run("fn bad(a = 1, b) { return b; }")).is_err());
+    }
+
+    #[test]
+    fn i_test_try_run() {
+        let mut i = Interpreter::new();
+        let err = i.try_run("1 + true;").unwrap_err();
+        assert!(err.contains("Cannot add"), "{}", err);
+        assert!(i.try_run("let = 4;").is_err());
+        assert!(i.try_run("fn f() { let local = 1; missing(); } f();").is_err());
+        assert_eq!(i.try_run("let local = 2; local + 1;"), Ok(RuntimeValue::Number(3.)));
+    }
+
+    /// Whole and fractional literals are the same `Number` type, so they mix
+    /// freely in comparisons, arithmetic and equality.
+    #[test]
+    fn i_test_mixed_whole_and_fractional_numbers() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.run("1 < 1.5;"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("2 >= 1.999;"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("1.5 > 2;"), RuntimeValue::Bool(false));
+        assert_eq!(i.run("2 + 0.5;"), RuntimeValue::Number(2.5));
+        assert_eq!(i.run("3 * 0.5;"), RuntimeValue::Number(1.5));
+        assert_eq!(i.run("0.5 - 1;"), RuntimeValue::Number(-0.5));
+        assert_eq!(i.run("2 == 2.0;"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("2.0 != 2;"), RuntimeValue::Bool(false));
+        assert_eq!(i.run("1.5 + 1.5 == 3;"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("toString(2.0);"), RuntimeValue::String("2".into()));
+    }
+
+    #[test]
+    fn i_test_arithmetic_results() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.run("4 / 2;"), RuntimeValue::Number(2.));
+        assert_eq!(i.run("7 / 2;"), RuntimeValue::Number(3.5));
+        assert_eq!(i.run("7 % 3;"), RuntimeValue::Number(1.));
+        assert_eq!(i.run("-7 % 3;"), RuntimeValue::Number(-1.));
+        assert_eq!(i.run("7.5 % 2;"), RuntimeValue::Number(1.5));
+        assert_eq!(i.run("floorDiv(7, 2);"), RuntimeValue::Number(3.));
+        assert_eq!(i.run("floorDiv(-7, 2);"), RuntimeValue::Number(-4.));
+        assert_eq!(i.run("2 ^ 10;"), RuntimeValue::Number(1024.));
+        assert_eq!(i.run("1 + 6 / 3 * 2 % 3;"), RuntimeValue::Number(2.));
+        // `//` starts a comment, leaving only the left operand
+        assert_eq!(i.run("7//2;\n;"), RuntimeValue::Number(7.));
+
+        assert_eq!(catch(|| i.run("1 / 0;")).unwrap_err().to_string(), "Cannot divide by zero: 1 / 0");
+        assert!(catch(|| i.run("floorDiv(1, 0);")).is_err());
+    }
+
+    #[test]
+    fn i_test_modulo() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.run("10 % 3;"), RuntimeValue::Number(1.));
+        assert_eq!(i.run("9 % 3;"), RuntimeValue::Number(0.));
+        assert_eq!(i.run("2 * 5 % 3;"), RuntimeValue::Number(1.));
+        assert_eq!(catch(|| i.run("5 % 0;")).unwrap_err().to_string(), "Cannot divide by zero: 5 % 0");
+        assert_eq!(catch(|| i.run("\"a\" % 2;")).unwrap_err().to_string(), "Cannot take the remainder of String(\"a\") and Number(2.0)");
+    }
+
+    #[test]
+    fn i_test_max_string_len() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.run(r#""ab" * 3;"#), RuntimeValue::String("ababab".into()));
+        let err = catch(|| i.run(r#""x" * 1e9;"#)).unwrap_err();
+        assert_eq!(err, RuntimeError::StringTooLong { length: 1_000_000_000, max: DEFAULT_MAX_STRING_LEN });
+
+        i.set_max_string_len(4);
+        assert_eq!(i.run(r#"2 * "ab";"#), RuntimeValue::String("abab".into()));
+        let err = catch(|| i.run(r#"3 * "ab";"#)).unwrap_err();
+        assert_eq!(err.to_string(), "String of 6 bytes exceeds the maximum length of 4 bytes");
+    }
+
+    #[test]
+    fn i_test_implicit_return() {
+        let mut i = Interpreter::new();
+        i.run("fn square(x) { x * x } fn explicit(x) { return x * x; } fn early(x) { if (x > 0) { return 1; } x * 0 - 1 }");
+        assert_eq!(i.run("square(4);"), RuntimeValue::Number(16.));
+        assert_eq!(i.run("square(4);"), i.run("explicit(4);"));
+        assert_eq!(i.run("early(5);"), RuntimeValue::Number(1.));
+        assert_eq!(i.run("early(-5);"), RuntimeValue::Number(-1.));
+        assert_eq!(i.run("fn none() { let unused = 1; } none();"), RuntimeValue::Null);
+    }
+
+    #[test]
+    fn i_test_variables_shadowing_functions() {
+        let output = SharedBuffer::new();
+        let mut i = Interpreter::with_options(InterpreterOptions { output: Some(Box::new(output.clone())), ..Default::default() });
+        i.run(r#"let print = "not a function"; print(print);"#);
+        assert_eq!(output.contents(), "not a function\n");
+
+        i.run("fn twice(x) { x * 2 } let twice = 5;");
+        assert_eq!(i.run("twice(twice);"), RuntimeValue::Number(10.));
+
+        let err = catch(|| i.run("let greet = 1; greet();")).unwrap_err();
+        assert_eq!(err.to_string(), "greet is a variable, not a function");
+        assert_eq!(i.run("len;"), RuntimeValue::Function("len".into()));
+        let err = catch(|| i.run("missing;")).unwrap_err();
+        assert_eq!(err.to_string(), "Variable missing does not exist");
+    }
+
+    #[test]
+    fn i_test_assert_throws() {
+        let mut i = Interpreter::new();
+        i.run("fn boom() { 1 / 0 } fn fine() { 1 / 2 }");
+        assert_eq!(i.run(r#"assertThrows("boom");"#), RuntimeValue::Bool(true));
+        let err = catch(|| i.run(r#"assertThrows("fine");"#)).unwrap_err();
+        assert_eq!(err.to_string(), "Assertion failed: fine did not throw");
+        assert_eq!(i.run(r#"let kept = 1; assertThrows("missing"); kept;"#), RuntimeValue::Number(1.));
+        assert!(i.check(r#"assertThrows("boom");"#).is_empty());
+    }
+
+    #[test]
+    fn i_test_structural_equality() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.run("[1, 2] == [1, 2];"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("[1, 2] == [2, 1];"), RuntimeValue::Bool(false));
+        assert_eq!(i.run("[1, [2, \"a\"]] != [1, [2, \"a\"]];"), RuntimeValue::Bool(false));
+        assert_eq!(i.run("[1] != [1, 1];"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("{ a: 1, b: [2] } == { b: [2], a: 1 };"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("{ a: 1 } == { a: 1, b: 2 };"), RuntimeValue::Bool(false));
+        assert_eq!(i.run("{ a: 1 } != { a: 2 };"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("[0.1 + 0.2] == [0.3];"), i.run("0.1 + 0.2 == 0.3;"));
+        assert_eq!(i.run("[nan] == [nan];"), RuntimeValue::Bool(false));
+        assert_eq!(i.run("[1] == 1;"), RuntimeValue::Bool(false));
+        assert_eq!(i.run("1 + 1 == 2;"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("1 < 2 == 2 < 3;"), RuntimeValue::Bool(true));
+    }
+
+    #[test]
+    fn i_test_display_precision() {
+        let output = SharedBuffer::new();
+        let mut i = Interpreter::with_options(InterpreterOptions { output: Some(Box::new(output.clone())), ..Default::default() });
+        i.run("print(1 / 3);");
+        i.set_display_precision(Some(2));
+        i.run(r#"print(1 / 3); print(0.1 + 0.2); print(2); print(-0.001); print([1.234, "1.234"]); print({ k: 2.499 });"#);
+        assert_eq!(i.run("toString(2 / 3);"), RuntimeValue::String("0.67".into()));
+        i.set_display_precision(None);
+        i.run("print(1 / 3);");
+        assert_eq!(output.contents(), "0.33333334\n0.33\n0.3\n2\n0\n[1.23, \"1.234\"]\n{\"k\": 2.5}\n0.33333334\n");
+    }
+
+    #[test]
+    fn i_test_conditional_and_coalesce_short_circuit() {
+        let mut i = Interpreter::new();
+        i.run("let hits = 0; fn bump() { hits = hits + 1; 1 }");
+        assert_eq!(i.run("true ? 1 : bump();"), RuntimeValue::Number(1.));
+        assert_eq!(i.run("false ? bump() : 2;"), RuntimeValue::Number(2.));
+        assert_eq!(i.run("5 ?? bump();"), RuntimeValue::Number(5.));
+        assert_eq!(i.run("hits;"), RuntimeValue::Number(0.));
+        assert_eq!(i.run("toNumber(\"x\") ?? bump();"), RuntimeValue::Number(1.));
+        assert_eq!(i.run("hits;"), RuntimeValue::Number(1.));
+        assert_eq!(i.run("1 > 2 ? \"a\" : 2 > 1 ? \"b\" : \"c\";"), RuntimeValue::String("b".into()));
+        let err = catch(|| i.run("1 ? 2 : 3;")).unwrap_err();
+        assert_eq!(err.to_string(), "Expression inside ternary must return a bool value");
+    }
+
+    #[test]
+    fn i_test_try_catch() {
+        let mut i = Interpreter::new();
+        i.run("let message = \"\"; let after = 0; try { let x = 1 / 0; after = 1; } catch (e) { message = e; }");
+        assert_eq!(i.run("message;"), RuntimeValue::String("Cannot divide by zero: 1 / 0".into()));
+        assert_eq!(i.run("after;"), RuntimeValue::Number(0.));
+        i.run("let y = 2; let z = y * 3;");
+        assert_eq!(i.run("z;"), RuntimeValue::Number(6.));
+        assert_eq!(i.run("try { 1; } catch (e) { 2; }"), RuntimeValue::Number(1.));
+        let mut i = Interpreter::with_options(InterpreterOptions { step_limit: Some(50), ..Default::default() });
+        let err = catch(|| i.run("try { do { 1; } while (true); } catch (e) { 2; }")).unwrap_err();
+        assert!(matches!(err, RuntimeError::StepLimitExceeded(50)));
+    }
+
+    #[test]
+    fn i_test_catch_name_cannot_shadow() {
+        let mut i = Interpreter::new();
+        let result = catch(|| i.run("let e = 1; try { throw 5; } catch (e) { e; }"));
+        assert_eq!(result, Err(RuntimeError::AlreadyDeclared("e".to_string())));
+        assert_eq!(i.run("try { throw 5; } catch (f) { f; }"), RuntimeValue::Number(5.));
+        assert_eq!(i.run("try { throw 6; } catch (f) { f; }"), RuntimeValue::Number(6.));
+
+        let mut i = Interpreter::with_options(InterpreterOptions { allow_shadowing: true, ..Default::default() });
+        assert_eq!(i.run("let e = 1; try { throw 5; } catch (e) { e; }"), RuntimeValue::Number(5.));
+        assert_eq!(i.run("e;"), RuntimeValue::Number(1.));
+    }
+
+    #[test]
+    fn i_test_throw() {
+        let mut i = Interpreter::new();
+        i.run("fn fail() { throw \"custom error\"; } let message = \"\";");
+        i.run("try { fail(); } catch (e) { message = e; }");
+        assert_eq!(i.run("message;"), RuntimeValue::String("custom error".into()));
+        assert_eq!(i.run("try { throw [1, 2]; } catch (e) { e; }"), i.run("[1, 2];"));
+        assert_eq!(i.try_run("throw 1 + 1;"), Err("2".to_string()));
+        let err = catch(|| i.run("fail();")).unwrap_err();
+        assert_eq!(err, RuntimeError::Thrown("custom error".into()));
+    }
+
+    #[test]
+    fn i_test_null_comparisons() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.run("null == null;"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("5 == null;"), RuntimeValue::Bool(false));
+        assert_eq!(i.run("null != \"\";"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("[null] == [null];"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("toNumber(\"x\") == null;"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("null ?? 3;"), RuntimeValue::Number(3.));
+        let err = catch(|| i.run("5 < null;")).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot order Number and Null, use == or != to compare with null");
+        assert!(catch(|| i.run("null >= null;")).is_err());
+    }
+
+    #[test]
+    fn i_test_run_collect() {
+        let mut i = Interpreter::new();
+        let values = i.run_collect("1; 2; let x = 3; x").unwrap();
+        assert_eq!(values, vec![RuntimeValue::Number(1.), RuntimeValue::Number(2.), RuntimeValue::Null, RuntimeValue::Number(3.)]);
+        let values = i.run_collect("f(); fn f() { 4 } return 5; 6;").unwrap();
+        assert_eq!(values, vec![RuntimeValue::Number(4.), RuntimeValue::Null, RuntimeValue::Number(5.)]);
+        assert_eq!(i.run_collect("1; 1 / 0;").unwrap_err().to_string(), "Cannot divide by zero: 1 / 0");
+    }
+
+    #[test]
+    fn i_test_destructuring_ignores_underscore() {
+        let mut i = Interpreter::new();
+        i.run("let [_, b] = [1, 2];");
+        assert_eq!(i.run("b;"), RuntimeValue::Number(2.));
+        assert!(i.try_run("_;").is_err());
+        i.run("let [first, [_, last]] = [3, [4, 5]]; let [_, _] = [6, 7]; let _ = 8;");
+        assert_eq!(i.run("[first, last];"), i.run("[3, 5];"));
+        assert!(i.try_run("_;").is_err());
+        assert_eq!(i.try_run("let [c, _] = [1];"), Err("Cannot destructure [1] into a pattern of 2 elements".to_string()));
+        assert_eq!(i.try_run("let [b, d] = [1, 2];"), Err("Variable b was already declared".to_string()));
+    }
+
+    #[test]
+    fn i_test_number_comparison() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.run("5 > 3;"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("2 < 1;"), RuntimeValue::Bool(false));
+        assert_eq!(i.run("1 + 1 > 1;"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("nan > 1;"), RuntimeValue::Bool(false));
+        assert_eq!(i.run("nan < 1;"), RuntimeValue::Bool(false));
+        let err = catch(|| i.run("true > 1;")).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot compare Bool and Number");
+        let err = catch(|| i.run("[1] < [2];")).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot compare Array and Array");
+    }
+
+    #[test]
+    fn i_test_chained_assignment() {
+        let mut i = Interpreter::new();
+        i.run("let a = 0; let b = 0; a = b = 7;");
+        assert_eq!(i.run("a;"), RuntimeValue::Number(7.));
+        assert_eq!(i.run("b;"), RuntimeValue::Number(7.));
+        assert_eq!(i.run("a = b = a + 1;"), RuntimeValue::Number(8.));
+        assert_eq!(i.run("[a, b];"), i.run("[8, 8];"));
+        assert_eq!(i.try_run("a = missing = 1;"), Err("Variable missing does not exist".to_string()));
+    }
+
+    #[test]
+    fn i_test_if_block_scope() {
+        let output = SharedBuffer::new();
+        let mut i = Interpreter::with_options(InterpreterOptions { output: Some(Box::new(output.clone())), ..Default::default() });
+        i.run("let x = 1; if (2 > 1) { x = 5; } print(x);");
+        assert_eq!(output.contents(), "5\n");
+        i.run("if (true) { let inner = 2; x = inner + x; } else { let other = 3; }");
+        assert_eq!(i.run("x;"), RuntimeValue::Number(7.));
+        assert!(i.try_run("inner;").is_err());
+        i.run("if (false) { 1; } else { let inner = 3; }");
+        assert!(i.try_run("inner;").is_err());
+        assert_eq!(i.run("if (true) { let y = 4; y * 2 }"), RuntimeValue::Number(8.));
+    }
+
+    #[test]
+    fn i_test_assignment_expression() {
+        let output = SharedBuffer::new();
+        let mut i = Interpreter::with_options(InterpreterOptions { output: Some(Box::new(output.clone())), ..Default::default() });
+        i.run("let x = 0; print(x = 5);");
+        assert_eq!(output.contents(), "5\n");
+        assert_eq!(i.run("x;"), RuntimeValue::Number(5.));
+        assert_eq!(i.run("(x = 2) + 1;"), RuntimeValue::Number(3.));
+        assert_eq!(i.run("x;"), RuntimeValue::Number(2.));
+        assert_eq!(i.run("let n = 0; do { n = n + 1; } while ((x = n * 10) < 30); x;"), RuntimeValue::Number(30.));
+        assert_eq!(i.run("let y = x = 1; y;"), RuntimeValue::Number(1.));
+    }
+
+    #[test]
+    fn i_test_else_if_chain() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.run("if (1 > 2) { \"yes\"; } else { \"no\"; }"), RuntimeValue::String("no".into()));
+        i.run("fn sign(n) { if (n > 0) { \"positive\" } else if (n < 0) { \"negative\" } else { \"zero\" } }");
+        assert_eq!(i.run("sign(3);"), RuntimeValue::String("positive".into()));
+        assert_eq!(i.run("sign(-3);"), RuntimeValue::String("negative".into()));
+        assert_eq!(i.run("sign(0);"), RuntimeValue::String("zero".into()));
+        assert_eq!(i.run("if (false) { 1; } else if (false) { 2; }"), RuntimeValue::Null);
+        assert_eq!(i.run("if (true) {} else { 1; }"), RuntimeValue::Null);
+    }
+
+    #[test]
+    fn i_test_floored_mod() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.run("mod(-1, 3);"), RuntimeValue::Number(2.));
+        assert_eq!(i.run("-1 % 3;"), RuntimeValue::Number(-1.));
+        assert_eq!(i.run("mod(1, -3);"), RuntimeValue::Number(-2.));
+        assert_eq!(i.run("mod(7, 3);"), RuntimeValue::Number(1.));
+        assert_eq!(i.run("mod(-6, 3);"), RuntimeValue::Number(0.));
+        assert_eq!(i.try_run("mod(1, 0);"), Err("Cannot divide by zero: mod(1, 0)".to_string()));
+    }
+
+    #[test]
+    fn i_test_while() {
+        let mut i = Interpreter::new();
+        i.run("let n = 5; let factorial = 1; let counter = 1; while (counter <= n) { factorial = factorial * counter; counter = counter + 1; }");
+        assert_eq!(i.run("factorial;"), RuntimeValue::Number(120.));
+        assert_eq!(i.run("counter;"), RuntimeValue::Number(6.));
+        i.run("let runs = 0; while (false) { runs = runs + 1; }");
+        assert_eq!(i.run("runs;"), RuntimeValue::Number(0.));
+        i.run("let k = 0; while (k < 3) { let step = 1; k = k + step; }");
+        assert_eq!(i.run("k;"), RuntimeValue::Number(3.));
+        assert_eq!(i.run("fn first_over(limit) { let x = 1; while (true) { x = x * 2; if (x > limit) { return x; } } } first_over(20);"), RuntimeValue::Number(32.));
+        let err = catch(|| i.run("while (1) { 1; }")).unwrap_err();
+        assert_eq!(err.to_string(), "Expression inside while must return a bool value");
+    }
+
+    #[test]
+    fn i_test_function_values() {
+        let mut i = Interpreter::new();
+        i.run("let upper = toUpper;");
+        assert_eq!(i.run("upper(\"hi\");"), RuntimeValue::String("HI".into()));
+        assert_eq!(i.run("upper;").to_string(), "<fn toUpper>");
+        i.run("fn double(x) { x * 2 } let f = double; let g = f;");
+        assert_eq!(i.run("g(4);"), RuntimeValue::Number(8.));
+        assert_eq!(i.run("[f == double, f == upper];"), i.run("[true, false];"));
+        i.run("fn boom() { 1 / 0 }");
+        assert_eq!(i.run("assertThrows(boom);"), RuntimeValue::Bool(true));
+        assert!(i.check("let h = len; h([1]);").is_empty());
+        assert_eq!(i.run("[upper, 1];").to_json(), "[null,1]");
+    }
+
+    #[test]
+    fn i_test_for() {
+        let mut i = Interpreter::new();
+        i.run("let total = 0; for (let i = 0; i < 10; i = i + 1) { total = total + i; }");
+        assert_eq!(i.run("total;"), RuntimeValue::Number(45.));
+        assert_eq!(i.try_run("i;"), Err("Variable i does not exist".to_string()));
+        i.run("for (let i = 0; i < 3; i = i + 1) { let squared = i * i; total = squared; }");
+        assert_eq!(i.run("total;"), RuntimeValue::Number(4.));
+        i.run("let n = 0; for (; n < 5;) { n = n + 2; }");
+        assert_eq!(i.run("n;"), RuntimeValue::Number(6.));
+        assert_eq!(i.run("fn find() { for (let i = 1;; i = i * 3) { if (i > 10) { return i; } } } find();"), RuntimeValue::Number(27.));
+        assert_eq!(i.try_run("for (let j = 0; 1; j = j + 1) {}"), Err("Expression inside for must return a bool value".to_string()));
+    }
+
+    #[test]
+    fn i_test_object_insertion_order() {
+        let mut i = Interpreter::new();
+        i.run("let o = { zeta: 1, alpha: 2, mid: 3, alpha: 4 };");
+        assert_eq!(i.run("keys(o);").to_string(), r#"["zeta", "alpha", "mid"]"#);
+        assert_eq!(i.run("o;").to_string(), r#"{"zeta": 1, "alpha": 4, "mid": 3}"#);
+        assert_eq!(i.run("o;").to_json(), r#"{"zeta":1,"alpha":4,"mid":3}"#);
+        assert_eq!(i.run("o == { mid: 3, alpha: 4, zeta: 1 };"), RuntimeValue::Bool(true));
+        assert!(i.try_run("keys([1]);").is_err());
+    }
+
+    #[test]
+    fn i_test_recursive_user_function() {
+        let mut i = Interpreter::new();
+        i.run("fn fib(n) { if (n < 2) { return n; } return fib(n - 1) + fib(n - 2); }");
+        assert_eq!(i.run("fib(10);"), RuntimeValue::Number(55.));
+        assert_eq!(i.run("fib(1);"), RuntimeValue::Number(1.));
+        i.run("fn add(a, b) { return a + b; }");
+        assert_eq!(i.run("add(fib(6), 2);"), RuntimeValue::Number(10.));
+        assert!(i.try_run("a;").is_err());
+        assert_eq!(i.try_run("add(1);"), Err("Function add expects 2 params, got 1".to_string()));
+    }
+
+    #[test]
+    fn i_test_early_return_from_loops() {
+        let mut i = Interpreter::new();
+        i.run("let after = 0;");
+        i.run("fn first_multiple(of, from) { let n = from; while (true) { if (n % of == 0) { return n; } n = n + 1; } after = 1; }");
+        assert_eq!(i.run("first_multiple(7, 20);"), RuntimeValue::Number(21.));
+        i.run("fn index_of(values, target) { for (let i = 0; i < len(values); i = i + 1) { if (at(values, i) == target) { return i; } } return -1; }");
+        assert_eq!(i.run("index_of([4, 5, 6], 5);"), RuntimeValue::Number(1.));
+        assert_eq!(i.run("index_of([4, 5, 6], 9);"), RuntimeValue::Number(-1.));
+        i.run("fn nested() { do { try { return \"inner\"; } catch (e) {} after = 2; } while (true); }");
+        assert_eq!(i.run("nested();"), RuntimeValue::String("inner".into()));
+        i.run("fn bare() { while (true) { return; } }");
+        assert_eq!(i.run("bare();"), RuntimeValue::Null);
+        assert_eq!(i.run("after;"), RuntimeValue::Number(0.));
+    }
+
+    #[test]
+    fn i_test_logical_operators() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.run("true && false;"), RuntimeValue::Bool(false));
+        assert_eq!(i.run("false || true;"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("1 < 2 && 2 < 3;"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("false && true || true;"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("true || false && false;"), RuntimeValue::Bool(true));
+        assert_eq!(i.run("false && undefined_variable;"), RuntimeValue::Bool(false));
+        assert_eq!(i.run("true || undefined_variable;"), RuntimeValue::Bool(true));
+        assert!(i.try_run("true && undefined_variable;").is_err());
+        assert_eq!(i.try_run("1 && true;"), Err("Operator && expects Bool operands, got Number".to_string()));
+        assert_eq!(i.try_run("false || \"a\";"), Err("Operator || expects Bool operands, got String".to_string()));
+        i.set_loose_conditions(true);
+        assert_eq!(i.run("1 && \"a\";"), RuntimeValue::Bool(true));
+    }
+
+    #[test]
+    fn i_test_lazy_function() {
+        let mut i = Interpreter::new();
+        i.add_lazy_function("lazyAnd", |args| {
+            if args.eval(0) != RuntimeValue::Bool(true) {
+                return RuntimeValue::Bool(false);
+            }
+            args.eval(1)
+        });
+        i.add_lazy_function("twice", |args| {
+            args.eval(0);
+            args.eval(0)
+        });
+        assert_eq!(i.run("lazyAnd(false, missing());"), RuntimeValue::Bool(false));
+        assert_eq!(i.run("lazyAnd(1 < 2, 2 < 3);"), RuntimeValue::Bool(true));
+        assert!(i.try_run("lazyAnd(true, missing());").is_err());
+
+        i.run("let count = 0; fn bump() { count = count + 1; }");
+        assert_eq!(i.run("lazyAnd(false, bump()); count;"), RuntimeValue::Number(0.));
+        assert_eq!(i.run("twice(bump());"), RuntimeValue::Number(2.));
+        assert_eq!(i.run("fn f(x) { twice(x = x + 1); } f(1);"), RuntimeValue::Number(3.));
+
+        let err = i.try_run("let g = lazyAnd; g(true, true);").unwrap_err();
+        assert_eq!(err, "Function lazyAnd takes its arguments unevaluated and can only be called by name");
+        assert!(i.function_names().contains(&"lazyAnd".to_string()));
+        assert!(i.check("lazyAnd(true, false, 1);").is_empty());
+        i.run("fn twice(x) { x * 2; }");
+        assert_eq!(i.run("twice(4);"), RuntimeValue::Number(8.));
+    }
+
+    #[test]
+    fn i_test_dump_env() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.dump_env(), "global {}\n");
+        let dump = i.run(r#"
+            let x = 1;
+            let name = "a \"b\"";
+            if (true) {
+                let list = [1, "two"];
+                if (x == 1) {
+                    let inner = null;
+                    dumpEnv();
+                }
+            }
+        "#);
+        assert_eq!(dump, RuntimeValue::String(concat!(
+            "scope 2 {\n  inner: null\n}\n",
+            "scope 1 {\n  list: [1, \"two\"]\n}\n",
+            "global {\n  name: \"a \\\"b\\\"\"\n  x: 1\n}\n"
+        ).into()));
+        assert_eq!(i.dump_env(), "global {\n  name: \"a \\\"b\\\"\"\n  x: 1\n}\n");
+        assert!(i.try_run("dumpEnv(1);").is_err());
+    }
+
+    #[test]
+    fn i_test_break_and_continue() {
+        let mut i = Interpreter::new();
+        i.run("let total = 0; for (let n = 0; n < 10; n = n + 1) { if (n % 2 == 1) { continue; } if (n > 6) { break; } total = total + n; }");
+        assert_eq!(i.run("total;"), RuntimeValue::Number(12.));
+        i.run("let k = 0; while (true) { k = k + 1; if (k == 3) { break } }");
+        assert_eq!(i.run("k;"), RuntimeValue::Number(3.));
+        i.run("let d = 0; do { d = d + 1; continue; d = 100; } while (d < 5);");
+        assert_eq!(i.run("d;"), RuntimeValue::Number(5.));
+        assert_eq!(i.run("fn first_even(list) { for (let n = 0; n < len(list); n = n + 1) { if (list[n] % 2 == 0) { return list[n]; } } } first_even([3, 5, 8, 10]);"), RuntimeValue::Number(8.));
+    }
+
+    #[test]
+    fn i_test_labeled_loops() {
+        let mut i = Interpreter::new();
+        i.run(r#"
+            let pairs = [];
+            outer: for (let a = 0; a < 3; a = a + 1) {
+                let b = 0;
+                while (true) {
+                    if (a == 1 && b == 1) { break outer; }
+                    if (b == 2) { break; }
+                    pairs = push(pairs, [a, b]);
+                    b = b + 1;
+                }
+            }
+        "#);
+        assert_eq!(i.run("pairs;").to_string(), "[[0, 0], [0, 1], [1, 0]]");
+
+        i.run(r#"
+            let skipped = [];
+            rows: for (let r = 0; r < 3; r = r + 1) {
+                cols: for (let c = 0; c < 3; c = c + 1) {
+                    if (c > r) { continue rows; }
+                    if (c == 1) { continue cols; }
+                    skipped = push(skipped, [r, c]);
+                }
+            }
+        "#);
+        assert_eq!(i.run("skipped;").to_string(), "[[0, 0], [1, 0], [2, 0], [2, 2]]");
+        assert_eq!(i.run("let w = 0; spin: do { w = w + 1; while (true) { break spin; } } while (true); w;"), RuntimeValue::Number(1.));
+
+        assert_eq!(i.try_run("loop: while (true) { break other; }"), Err("Unknown label other in break".to_string()));
+        assert_eq!(i.try_run("continue;"), Err("continue is only allowed inside a loop".to_string()));
+        assert_eq!(i.try_run("while (true) { fn f() { break; } }"), Err("break is only allowed inside a loop".to_string()));
+        assert_eq!(i.try_run("a: while (true) { a: while (true) { } }"), Err("Label a is already used by an enclosing loop".to_string()));
+        assert_eq!(i.try_run("a: if (true) { }"), Err("Only loops can be labeled, got Identifier after label a".to_string()));
+    }
+
+    #[test]
+    fn i_test_typeof() {
+        let mut i = Interpreter::new();
+        assert_eq!(i.run("typeof(1.5);"), RuntimeValue::String("number".into()));
+        assert_eq!(i.run("typeof(\"\" + 1);"), RuntimeValue::String("string".into()));
+        assert_eq!(i.run("typeof([1]);"), RuntimeValue::String("array".into()));
+        assert_eq!(i.run("typeof({ a: [1] });"), RuntimeValue::String("object".into()));
+        assert_eq!(i.run("typeof(len);"), RuntimeValue::String("function".into()));
+        assert_eq!(i.run("typeof(null) == \"null\";"), RuntimeValue::Bool(true));
+    }
+
+    #[test]
+    fn i_test_assert_throws_passes_on_limits() {
+        let mut i = Interpreter::with_options(InterpreterOptions { step_limit: Some(200), ..Default::default() });
+        i.run("fn spin() { while (true) { } }");
+        assert!(matches!(catch(|| i.run("assertThrows(spin);")), Err(RuntimeError::StepLimitExceeded(200))));
+
+        i.run("fn fail() { throw [1, 2]; }");
+        assert_eq!(i.run("assertThrows(fail);"), RuntimeValue::Bool(true));
+        assert_eq!(i.thrown, None);
+    }
+}
@@ -4,4 +4,9 @@ mod panics;
 pub mod parser;
 pub mod interpreter;
 pub mod func;
-pub mod env;
\ No newline at end of file
+pub mod env;
+pub mod error;
+pub mod printer;
+pub mod checker;
+pub mod repl;
+pub mod visitor;
@@ -1,11 +1,96 @@
+use std::{any::Any, cell::Cell, panic::{self, AssertUnwindSafe}, sync::Once};
+
+use super::error::RuntimeError;
+
 pub fn unexpected_eof(pos: &u32) -> ! {
-    panic!("Unexpected end of input at position {}", pos);
+    syntax_error(format!("Unexpected end of input at position {}", pos), pos);
 }
 
 pub fn unexpected_token(token: &str, pos: &u32) -> ! {
-    panic!("Unexpected token '{}' at position {}", token, pos);
+    syntax_error(format!("Unexpected token '{}' at position {}", token, pos), pos);
+}
+
+/// Like `unexpected_token`, adding a hint for characters that are commonly
+/// typed by mistake.
+pub fn unexpected_character(character: &str, pos: &u32) -> ! {
+    let hint = match character {
+        "\\" => Some("escape sequences are only valid inside strings"),
+        "&" => Some("did you mean '&&'?"),
+        "|" => Some("did you mean '||'?"),
+        _ => None
+    };
+    match hint {
+        Some(hint) => syntax_error(format!("Unexpected token '{}' at position {}: {}", character, pos, hint), pos),
+        None => unexpected_token(character, pos)
+    }
+}
+
+pub fn unknown_escape(sequence: &str, pos: &u32) -> ! {
+    syntax_error(format!("Unknown escape sequence '\\{}' at position {}", sequence, pos), pos);
+}
+
+pub fn invalid_number(pos: &u32) -> ! {
+    syntax_error(format!("Invalid format number at position {}", pos), pos);
+}
+
+fn syntax_error(message: String, pos: &u32) -> ! {
+    runtime_error(RuntimeError::Syntax { message, pos: *pos as usize });
 }
 
 pub fn casting_error(datatype: &str) -> ! {
     panic!("Cannot parse to {}", datatype);
-}
\ No newline at end of file
+}
+
+pub fn runtime_error(error: RuntimeError) -> ! {
+    install_hook();
+    panic::panic_any(error);
+}
+
+thread_local! {
+    static CATCH_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+/// Wraps the panic hook so it stays quiet while a panic is being caught and
+/// prints `RuntimeError` payloads with their message.
+fn install_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if CATCH_DEPTH.with(|depth| depth.get()) > 0 {
+                return;
+            }
+            match info.payload().downcast_ref::<RuntimeError>() {
+                Some(error) => eprintln!("{}", error),
+                None => default_hook(info)
+            }
+        }));
+    });
+}
+
+/// Runs `f`, turning any panic raised inside it into a `RuntimeError`.
+/// While catching, the panic hook stays quiet so the error is only reported
+/// through the returned `Result`.
+pub fn catch<T>(f: impl FnOnce() -> T) -> Result<T, RuntimeError> {
+    install_hook();
+    CATCH_DEPTH.with(|depth| depth.set(depth.get() + 1));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCH_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    result.map_err(to_runtime_error)
+}
+
+fn to_runtime_error(payload: Box<dyn Any + Send>) -> RuntimeError {
+    let payload = match payload.downcast::<RuntimeError>() {
+        Ok(error) => return *error,
+        Err(payload) => payload
+    };
+    let payload = match payload.downcast::<String>() {
+        Ok(message) => return RuntimeError::Message(*message),
+        Err(payload) => payload
+    };
+    match payload.downcast::<&str>() {
+        Ok(message) => RuntimeError::Message(message.to_string()),
+        Err(_) => RuntimeError::Message("Unknown error".to_string())
+    }
+}
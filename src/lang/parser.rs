@@ -1,16 +1,19 @@
-use std::rc::Rc;
+use std::{mem, ops::RangeInclusive, rc::Rc};
 
 use super::tokenizer::{Token, TokenKind, Tokenizer};
 
 pub struct Parser {
     pos: usize,
-    tokens: Vec<Token>
+    tokens: Vec<Token>,
+    /// The labels of the loops enclosing the statement being parsed,
+    /// innermost last, with `None` for an unlabeled loop.
+    loops: Vec<Option<String>>
 }
 
 impl Parser {
     pub fn new(text: &str) -> Self {
-        let tokens = Tokenizer::new(text).tokenize();
-        Parser { tokens, pos: 0 }
+        let tokens = Tokenizer::new(text, false).tokenize();
+        Parser { tokens, pos: 0, loops: vec![] }
     }
 
     pub fn parse(&mut self) -> Vec<Rc<ASTNode>> {
@@ -21,6 +24,17 @@ impl Parser {
         tokens
     }
 
+    pub fn parse_expression(&mut self) -> Rc<ASTNode> {
+        let expression = self.parse_conditional_expression();
+        if !self.is_eof() && self.current().kind() == TokenKind::SemiColon {
+            self.advance(Some(TokenKind::SemiColon));
+        }
+        if !self.is_eof() {
+            panic!("Unexpected token {:?} after expression", self.current().kind());
+        }
+        expression
+    }
+
     fn current(&self) -> Token {
         self.tokens[self.pos].clone()
     }
@@ -60,6 +74,10 @@ impl Parser {
             token.kind(), 
             TokenKind::NumberLiteral | 
             TokenKind::StringLiteral | 
+            TokenKind::BoolLiteral |
+            TokenKind::NullLiteral |
+            TokenKind::LeftSqBrace | 
+            TokenKind::LeftCurlyBrace | 
             TokenKind::LeftParen | 
             TokenKind::Identifier |
             TokenKind::SubOp |
@@ -75,26 +93,36 @@ impl Parser {
                 eat_semicolon = false;
                 self.parse_if_stmt()
             },
-            Token::Identifier { value } => {
-                if self.expect(TokenKind::LeftParen) {
-                    self.parse_function()
-                } else if self.expect(TokenKind::EqOp) {
-                    self.parse_var_assignment()
-                } else {
-                    self.advance(None);
-                    Rc::new(ASTNode::Identifier { name: value })
-                }
+            Token::Identifier { value } if value == "while" || value == "for" || value == "do" => {
+                eat_semicolon = false;
+                self.parse_loop(None)
+            },
+            Token::Identifier { value } if value == "try" => {
+                eat_semicolon = false;
+                self.parse_try_catch()
+            },
+            Token::Identifier { value } if value == "return" => self.parse_return_stmt(),
+            Token::Identifier { value } if value == "throw" => self.parse_throw_stmt(),
+            Token::Identifier { value } if value == "break" || value == "continue" => self.parse_jump_stmt(),
+            Token::Identifier { value } if value == "fn" => {
+                eat_semicolon = false;
+                self.parse_function_declaration()
+            },
+            Token::Identifier { .. } if self.expect(TokenKind::Colon) => {
+                eat_semicolon = false;
+                self.parse_labeled_loop()
             },
+            Token::Identifier { .. } if self.expect(TokenKind::EqOp) => self.parse_var_assignment(),
             value => {
-                if self.is_expr(&value) {
-                    self.parse_bool_expression();
+                if !self.is_expr(&value) {
+                    panic!("Not recognized token {:?}!", value.kind())
                 }
-                panic!("Not recognized token!")
+                self.parse_conditional_expression()
             }
         };
-        if eat_semicolon {
-            self.advance(Some(TokenKind::SemiColon));
-        } else if self.current().kind() == TokenKind::SemiColon {
+        // the last statement of a block or of the program may leave out its semicolon
+        let ends_block = self.is_eof() || self.current().kind() == TokenKind::RightCurlyBrace;
+        if (eat_semicolon && !ends_block) || self.current().kind() == TokenKind::SemiColon {
             self.advance(Some(TokenKind::SemiColon));
         }
         token
@@ -104,7 +132,7 @@ impl Parser {
         match self.current() {
             Token::LeftParen => {
                 self.advance(None);
-                let node = self.parse_bool_expression();
+                let node = self.parse_conditional_expression();
                 self.advance(Some(TokenKind::RightParen));
                 node
             },
@@ -116,9 +144,24 @@ impl Parser {
                 self.advance(None);
                 Rc::new(ASTNode::Bool(value))
             },
-            Token::StringLiteral { value } => {
+            Token::NullLiteral => {
+                self.advance(None);
+                Rc::new(ASTNode::Null)
+            },
+            Token::StringLiteral { mut value } => {
                 self.advance(None);
-                Rc::new(ASTNode::String(value))
+                // adjacent literals are joined, so long strings can span lines
+                while let Token::StringLiteral { value: next } = self.current() {
+                    self.advance(None);
+                    value.push_str(&next);
+                }
+                Rc::new(ASTNode::String(value.into()))
+            },
+            Token::LeftSqBrace => {
+                self.parse_array_literal()
+            },
+            Token::LeftCurlyBrace => {
+                self.parse_object_literal()
             },
             Token::SubOp | Token::AddOp => {
                 self.parse_unary_expression()
@@ -140,10 +183,44 @@ impl Parser {
         }
     }
 
+    fn parse_array_literal(&mut self) -> Rc<ASTNode> {
+        self.advance(Some(TokenKind::LeftSqBrace));
+        let mut elements: Vec<Rc<ASTNode>> = vec![];
+        while !self.is_eof() && self.current().kind() != TokenKind::RightSqBrace {
+            elements.push(self.parse_conditional_expression());
+            if self.current().kind() == TokenKind::RightSqBrace {
+                break;
+            }
+            self.advance(Some(TokenKind::Comma));
+        }
+        self.advance(Some(TokenKind::RightSqBrace));
+        Rc::new(ASTNode::ArrayLiteral { elements })
+    }
+
+    fn parse_object_literal(&mut self) -> Rc<ASTNode> {
+        self.advance(Some(TokenKind::LeftCurlyBrace));
+        let mut entries: Vec<(String, Rc<ASTNode>)> = vec![];
+        while !self.is_eof() && self.current().kind() != TokenKind::RightCurlyBrace {
+            let key = match self.advance(None) {
+                Token::Identifier { value } | Token::StringLiteral { value } => value,
+                token => panic!("Expected an object key, got {:?}", token.kind())
+            };
+            self.advance(Some(TokenKind::Colon));
+            entries.push((key, self.parse_conditional_expression()));
+            if self.current().kind() == TokenKind::RightCurlyBrace {
+                break;
+            }
+            self.advance(Some(TokenKind::Comma));
+        }
+        self.advance(Some(TokenKind::RightCurlyBrace));
+        Rc::new(ASTNode::ObjectLiteral { entries })
+    }
+
     fn parse_var_assignment(&mut self) -> Rc<ASTNode> {
         let var_name = self.advance(Some(TokenKind::Identifier));
         self.advance(Some(TokenKind::EqOp));
-        let value = self.parse_sum_expression();
+        // the value may be another assignment, so `a = b = 5` assigns `b` first
+        let value = self.parse_conditional_expression();
         Rc::new(
             ASTNode::VarAssignment { name: var_name.as_string(), value }
         )
@@ -151,11 +228,11 @@ impl Parser {
 
     fn parse_var_declaration(&mut self) -> Rc<ASTNode> {
         self.advance(Some(TokenKind::Identifier));
-        let var_name = self.advance(Some(TokenKind::Identifier));
+        let pattern = self.parse_pattern();
         self.advance(Some(TokenKind::EqOp));
-        let value = self.parse_sum_expression();
+        let value = self.parse_conditional_expression();
         Rc::new(
-            ASTNode::VarDeclaration { name: var_name.as_string(), value }
+            ASTNode::VarDeclaration { pattern, value }
         )
     }
 
@@ -164,15 +241,209 @@ impl Parser {
         self.advance(Some(TokenKind::LeftParen));
         let args = self.parse_args();
         self.advance(Some(TokenKind::RightParen));
-        return Rc::new(
+        Rc::new(
             ASTNode::FunctionCall { name: ident.as_string(), args }
-        );
+        )
+    }
+
+    fn parse_function_declaration(&mut self) -> Rc<ASTNode> {
+        self.advance(Some(TokenKind::Identifier));
+        let name = self.advance(Some(TokenKind::Identifier));
+        self.advance(Some(TokenKind::LeftParen));
+        let mut params: Vec<Param> = vec![];
+        while !self.is_eof() && self.current().kind() != TokenKind::RightParen {
+            let pattern = self.parse_pattern();
+            let default = if self.current().kind() == TokenKind::EqOp {
+                self.advance(Some(TokenKind::EqOp));
+                Some(self.parse_conditional_expression())
+            } else {
+                None
+            };
+            if default.is_none() && params.last().is_some_and(|param| param.default.is_some()) {
+                panic!("Param without a default value cannot follow one with a default in function {}", name.as_string());
+            }
+            params.push(Param { pattern, default });
+            if self.current().kind() == TokenKind::RightParen {
+                break;
+            }
+            self.advance(Some(TokenKind::Comma));
+        }
+        self.advance(Some(TokenKind::RightParen));
+        // a break inside the body cannot reach the loops around the declaration
+        let enclosing_loops = mem::take(&mut self.loops);
+        let body = self.parse_block();
+        self.loops = enclosing_loops;
+        Rc::new(
+            ASTNode::FunctionDeclaration { name: name.as_string(), params, body }
+        )
+    }
+
+    fn parse_pattern(&mut self) -> Pattern {
+        if self.current().kind() != TokenKind::LeftSqBrace {
+            let name = self.advance(Some(TokenKind::Identifier)).as_string();
+            return match name.as_str() {
+                "_" => Pattern::Ignore,
+                _ => Pattern::Name(name)
+            };
+        }
+        self.advance(Some(TokenKind::LeftSqBrace));
+        let mut elements: Vec<Pattern> = vec![];
+        while !self.is_eof() && self.current().kind() != TokenKind::RightSqBrace {
+            elements.push(self.parse_pattern());
+            if self.current().kind() == TokenKind::RightSqBrace {
+                break;
+            }
+            self.advance(Some(TokenKind::Comma));
+        }
+        self.advance(Some(TokenKind::RightSqBrace));
+        Pattern::Array(elements)
+    }
+
+    fn parse_return_stmt(&mut self) -> Rc<ASTNode> {
+        self.advance(Some(TokenKind::Identifier));
+        let value = match self.current().kind() {
+            TokenKind::SemiColon => None,
+            _ => Some(self.parse_conditional_expression())
+        };
+        Rc::new(ASTNode::ReturnStmt { value })
+    }
+
+    fn parse_throw_stmt(&mut self) -> Rc<ASTNode> {
+        self.advance(Some(TokenKind::Identifier));
+        let value = self.parse_conditional_expression();
+        Rc::new(ASTNode::Throw { value })
+    }
+
+    /// `name: loop`, where the loop is a while, for or do-while.
+    fn parse_labeled_loop(&mut self) -> Rc<ASTNode> {
+        let label = self.advance(Some(TokenKind::Identifier)).as_string();
+        self.advance(Some(TokenKind::Colon));
+        if self.loops.contains(&Some(label.clone())) {
+            panic!("Label {} is already used by an enclosing loop", label);
+        }
+        match self.current() {
+            Token::Identifier { value } if value == "while" || value == "for" || value == "do" => self.parse_loop(Some(label)),
+            token => panic!("Only loops can be labeled, got {:?} after label {}", token.kind(), label)
+        }
+    }
+
+    fn parse_loop(&mut self, label: Option<String>) -> Rc<ASTNode> {
+        match self.current().as_string().as_str() {
+            "while" => self.parse_while_stmt(label),
+            "for" => self.parse_for_stmt(label),
+            _ => self.parse_do_while_stmt(label)
+        }
+    }
+
+    /// Parses the body of a loop labeled `label`, letting `break` and
+    /// `continue` inside it target the loop.
+    fn parse_loop_body(&mut self, label: &Option<String>) -> Vec<Rc<ASTNode>> {
+        self.loops.push(label.clone());
+        let body = self.parse_block();
+        self.loops.pop();
+        body
+    }
+
+    /// `break` or `continue`, optionally naming the enclosing loop they target.
+    fn parse_jump_stmt(&mut self) -> Rc<ASTNode> {
+        let keyword = self.advance(Some(TokenKind::Identifier)).as_string();
+        let label = match self.current() {
+            Token::Identifier { value } => {
+                self.advance(Some(TokenKind::Identifier));
+                Some(value)
+            },
+            _ => None
+        };
+        match &label {
+            _ if self.loops.is_empty() => panic!("{} is only allowed inside a loop", keyword),
+            Some(name) if !self.loops.contains(&label) => panic!("Unknown label {} in {}", name, keyword),
+            _ => {}
+        }
+        match keyword.as_str() {
+            "break" => Rc::new(ASTNode::Break { label }),
+            _ => Rc::new(ASTNode::Continue { label })
+        }
+    }
+
+    fn parse_while_stmt(&mut self, label: Option<String>) -> Rc<ASTNode> {
+        self.advance(Some(TokenKind::Identifier));
+        let condition = self.parse_expr();
+        let body = self.parse_loop_body(&label);
+        Rc::new(ASTNode::WhileStmt { label, condition, body })
+    }
+
+    /// `for (init; condition; update) { body }`, where any of the three
+    /// clauses can be left empty.
+    fn parse_for_stmt(&mut self, label: Option<String>) -> Rc<ASTNode> {
+        self.advance(Some(TokenKind::Identifier));
+        self.advance(Some(TokenKind::LeftParen));
+        let init = match self.current().kind() {
+            TokenKind::SemiColon => {
+                self.advance(Some(TokenKind::SemiColon));
+                None
+            },
+            _ => Some(self.parse_expr_or_stmt())
+        };
+        let condition = match self.current().kind() {
+            TokenKind::SemiColon => None,
+            _ => Some(self.parse_conditional_expression())
+        };
+        self.advance(Some(TokenKind::SemiColon));
+        let update = match self.current().kind() {
+            TokenKind::RightParen => None,
+            _ => Some(self.parse_conditional_expression())
+        };
+        self.advance(Some(TokenKind::RightParen));
+        let body = self.parse_loop_body(&label);
+        Rc::new(ASTNode::ForStmt { label, init, condition, update, body })
+    }
+
+    fn parse_do_while_stmt(&mut self, label: Option<String>) -> Rc<ASTNode> {
+        self.advance(Some(TokenKind::Identifier));
+        let body = self.parse_loop_body(&label);
+        let keyword = self.advance(Some(TokenKind::Identifier));
+        if keyword.as_string() != "while" {
+            panic!("Expected while after do block, got {}", keyword.as_string());
+        }
+        self.advance(Some(TokenKind::LeftParen));
+        let condition = self.parse_conditional_expression();
+        self.advance(Some(TokenKind::RightParen));
+        Rc::new(ASTNode::DoWhileStmt { label, body, condition })
+    }
+
+    fn parse_try_catch(&mut self) -> Rc<ASTNode> {
+        self.advance(Some(TokenKind::Identifier));
+        let try_block = self.parse_block();
+        let keyword = self.advance(Some(TokenKind::Identifier));
+        if keyword.as_string() != "catch" {
+            panic!("Expected catch after try block, got {}", keyword.as_string());
+        }
+        self.advance(Some(TokenKind::LeftParen));
+        let error_name = self.advance(Some(TokenKind::Identifier)).as_string();
+        self.advance(Some(TokenKind::RightParen));
+        let catch_block = self.parse_block();
+        Rc::new(ASTNode::TryCatch { try_block, error_name, catch_block })
+    }
+
+    fn parse_block(&mut self) -> Vec<Rc<ASTNode>> {
+        self.advance(Some(TokenKind::LeftCurlyBrace));
+        let mut block: Vec<Rc<ASTNode>> = vec![];
+        while !self.is_eof() && self.current().kind() != TokenKind::RightCurlyBrace {
+            block.push(self.parse_expr_or_stmt());
+        }
+        self.advance(Some(TokenKind::RightCurlyBrace));
+        block
     }
 
     fn parse_args(&mut self) -> Vec<Rc<ASTNode>> {
         let mut args: Vec<Rc<ASTNode>> = Vec::new();
         while !self.is_eof() && self.current().kind() != TokenKind::RightParen {
-            let arg = self.parse_sum_expression();
+            let arg = if self.current().kind() == TokenKind::Spread {
+                self.advance(Some(TokenKind::Spread));
+                Rc::new(ASTNode::Spread { expr: self.parse_conditional_expression() })
+            } else {
+                self.parse_conditional_expression()
+            };
             args.push(arg);
             if self.current().kind() == TokenKind::RightParen {
                 break;
@@ -182,27 +453,108 @@ impl Parser {
         args
     }
 
+    fn parse_postfix_expression(&mut self) -> Rc<ASTNode> {
+        let mut receiver = self.parse_expr();
+        while !self.is_eof() && matches!(self.current().kind(), TokenKind::Dot | TokenKind::LeftSqBrace) {
+            if self.current().kind() == TokenKind::LeftSqBrace {
+                self.advance(Some(TokenKind::LeftSqBrace));
+                let index = self.parse_conditional_expression();
+                self.advance(Some(TokenKind::RightSqBrace));
+                receiver = Rc::new(ASTNode::Index { target: receiver, index });
+                continue;
+            }
+            self.advance(Some(TokenKind::Dot));
+            let method = self.advance(Some(TokenKind::Identifier));
+            self.advance(Some(TokenKind::LeftParen));
+            let args = self.parse_args();
+            self.advance(Some(TokenKind::RightParen));
+            receiver = Rc::new(ASTNode::MethodCall { receiver, name: method.as_string(), args })
+        }
+        receiver
+    }
+
     fn parse_pow_expression(&mut self) -> Rc<ASTNode> {
-        let mut left = self.parse_expr();
+        let mut left = self.parse_postfix_expression();
         while !self.is_eof() && self.current().kind() == TokenKind::PowOp {
             self.advance(Some(TokenKind::PowOp));
-            let right = self.parse_expr();
-            left = Rc::new(ASTNode::BinaryExpression { left, right, operator: '^' })
+            let right = self.parse_postfix_expression();
+            left = Rc::new(ASTNode::BinaryExpression { left, right, operator: "^" })
+        }
+        left
+    }
+
+    /// Parses a full expression. An assignment is an expression too, yielding
+    /// the assigned value, so it can appear as an argument or inside parens.
+    fn parse_conditional_expression(&mut self) -> Rc<ASTNode> {
+        if matches!(self.current(), Token::Identifier { .. }) && self.expect(TokenKind::EqOp) {
+            return self.parse_var_assignment();
+        }
+        let condition = self.parse_coalesce_expression();
+        if self.is_eof() || self.current().kind() != TokenKind::QuestionMark {
+            return condition;
+        }
+        self.advance(Some(TokenKind::QuestionMark));
+        let when_true = self.parse_conditional_expression();
+        self.advance(Some(TokenKind::Colon));
+        let when_false = self.parse_conditional_expression();
+        Rc::new(ASTNode::ConditionalExpression { condition, when_true, when_false })
+    }
+
+    fn parse_coalesce_expression(&mut self) -> Rc<ASTNode> {
+        let mut left = self.parse_logical_or_expression();
+        while !self.is_eof() && self.current().kind() == TokenKind::NullishOp {
+            self.advance(Some(TokenKind::NullishOp));
+            let right = self.parse_logical_or_expression();
+            left = Rc::new(ASTNode::BinaryExpression { left, right, operator: "??" })
+        }
+        left
+    }
+
+    fn parse_logical_or_expression(&mut self) -> Rc<ASTNode> {
+        let mut left = self.parse_logical_and_expression();
+        while !self.is_eof() && self.current().kind() == TokenKind::OrOp {
+            self.advance(Some(TokenKind::OrOp));
+            let right = self.parse_logical_and_expression();
+            left = Rc::new(ASTNode::BinaryExpression { left, right, operator: "||" })
+        }
+        left
+    }
+
+    fn parse_logical_and_expression(&mut self) -> Rc<ASTNode> {
+        let mut left = self.parse_bool_expression();
+        while !self.is_eof() && self.current().kind() == TokenKind::AndOp {
+            self.advance(Some(TokenKind::AndOp));
+            let right = self.parse_bool_expression();
+            left = Rc::new(ASTNode::BinaryExpression { left, right, operator: "&&" })
         }
         left
     }
 
     fn parse_bool_expression(&mut self) -> Rc<ASTNode> {
-        let mut left = self.parse_sum_expression();
-        while !self.is_eof() && (self.current().kind() == TokenKind::GtOp || self.current().kind() == TokenKind::LtOp) {
-            let expect = match self.current().kind() {
-                TokenKind::GtOp => Some(TokenKind::GtOp),
-                _ => Some(TokenKind::LtOp)
+        let mut left = self.parse_comparison_expression();
+        while !self.is_eof() && matches!(self.current().kind(), TokenKind::EqEqOp | TokenKind::NotEqOp) {
+            let expect = Some(self.current().kind());
+            let operator = match self.advance(expect) {
+                Token::EqEqOp => "==",
+                Token::NotEqOp => "!=",
+                _ => unreachable!("Unexpected operator")
             };
+            let right = self.parse_comparison_expression();
+            left = Rc::new(ASTNode::BinaryExpression { left, right, operator })
+        }
+        left
+    }
+
+    fn parse_comparison_expression(&mut self) -> Rc<ASTNode> {
+        let mut left = self.parse_sum_expression();
+        while !self.is_eof() && matches!(self.current().kind(), TokenKind::GtOp | TokenKind::LtOp | TokenKind::GtEqOp | TokenKind::LtEqOp) {
+            let expect = Some(self.current().kind());
             let math_op = self.advance(expect);
-            let operator =  match math_op {
-                Token::GtOp => '>',
-                Token::LtOp => '<',
+            let operator = match math_op {
+                Token::GtOp => ">",
+                Token::LtOp => "<",
+                Token::GtEqOp => ">=",
+                Token::LtEqOp => "<=",
                 _ => unreachable!("Unexpected operator")
             };
             let right = self.parse_sum_expression();
@@ -220,8 +572,8 @@ impl Parser {
             };
             let math_op = self.advance(expect);
             let operator = match math_op {
-                Token::AddOp => '+',
-                Token::SubOp => '-',
+                Token::AddOp => "+",
+                Token::SubOp => "-",
                 _ => unreachable!("Unexpected operator")
             };
             let right = self.parse_mul_expression();
@@ -232,10 +584,17 @@ impl Parser {
 
     fn parse_mul_expression(&mut self) -> Rc<ASTNode> {
         let mut left = self.parse_pow_expression();
-        while !self.is_eof() && self.current().kind() == TokenKind::MulOp {
-            self.advance(Some(TokenKind::MulOp));
+        while !self.is_eof() && matches!(self.current().kind(), TokenKind::MulOp | TokenKind::DivOp | TokenKind::ModOp) {
+            let expect = Some(self.current().kind());
+            let math_op = self.advance(expect);
+            let operator = match math_op {
+                Token::MulOp => "*",
+                Token::DivOp => "/",
+                Token::ModOp => "%",
+                _ => unreachable!("Unexpected operator")
+            };
             let right = self.parse_pow_expression();
-            left = Rc::new(ASTNode::BinaryExpression { left, right, operator: '*' })
+            left = Rc::new(ASTNode::BinaryExpression { left, right, operator })
         }
         left
     }
@@ -247,7 +606,7 @@ impl Parser {
             Token::AddOp => '+',
             _ => unreachable!("Unexpected sign")
         };
-        let expression = self.parse_expr();
+        let expression = self.parse_postfix_expression();
         Rc::new(
             ASTNode::UnaryExpression { sign, expr: expression }
         )
@@ -256,33 +615,16 @@ impl Parser {
     fn parse_if_stmt(&mut self) -> Rc<ASTNode> {
         self.advance(Some(TokenKind::Identifier));
         let expr = self.parse_expr();
-        self.advance(Some(TokenKind::LeftCurlyBrace));
-        let mut true_block : Vec<Rc<ASTNode>> = vec![];
+        let true_block = self.parse_block();
         let mut false_block : Option<Vec<Rc<ASTNode>>> = None;
-        loop {
-            //question: Check for EOF?
-            let token = self.parse_expr_or_stmt();
-            true_block.push(token);
-            if self.current().kind() == TokenKind::RightCurlyBrace {
-                break;
-            }
-        }
-        self.advance(Some(TokenKind::RightCurlyBrace));
-        if self.current().kind() == TokenKind::Identifier && self.current().as_string() == "else" {
+        if !self.is_eof() && self.current().kind() == TokenKind::Identifier && self.current().as_string() == "else" {
             self.advance(Some(TokenKind::Identifier));
-            if(self.current().kind() == TokenKind::Identifier && self.current().as_string() == "if") {
-                return self.parse_if_stmt();
+            // `else if` nests the following if as the whole else block
+            if self.current().kind() == TokenKind::Identifier && self.current().as_string() == "if" {
+                false_block = Some(vec![self.parse_if_stmt()]);
+            } else {
+                false_block = Some(self.parse_block());
             }
-            self.advance(Some(TokenKind::LeftCurlyBrace));
-            loop {
-                //question: check for EOF?
-                let token = self.parse_expr_or_stmt();
-                false_block.get_or_insert_with(Vec::new).push(token);
-                if self.current().kind() == TokenKind::RightCurlyBrace {
-                    break;
-                }
-            }
-            self.advance(Some(TokenKind::RightCurlyBrace));
         }
         Rc::new(
             ASTNode::IfStmt { expr, true_block, false_block }
@@ -294,7 +636,14 @@ impl Parser {
 pub enum ASTNode {
     Number(f32),
     Bool(bool),
-    String(String),
+    Null,
+    String(Rc<str>),
+    ArrayLiteral {
+        elements: Vec<Rc<ASTNode>>
+    },
+    ObjectLiteral {
+        entries: Vec<(String, Rc<ASTNode>)>
+    },
     Identifier {
         name: String
     },
@@ -302,17 +651,38 @@ pub enum ASTNode {
         name: String,
         args: Vec<Rc<ASTNode>>
     },
+    MethodCall {
+        receiver: Rc<ASTNode>,
+        name: String,
+        args: Vec<Rc<ASTNode>>
+    },
+    /// `target[index]`, reading an element of an array or a character of a
+    /// string. Negative indexes count from the end.
+    Index {
+        target: Rc<ASTNode>,
+        index: Rc<ASTNode>
+    },
     BinaryExpression {
         left: Rc<ASTNode>,
         right: Rc<ASTNode>,
-        operator: char,
+        operator: &'static str,
     },
     UnaryExpression {
         sign: char,
         expr: Rc<ASTNode>
     },
+    /// `...expr` inside an argument list, expanding an array into arguments.
+    Spread {
+        expr: Rc<ASTNode>
+    },
+    /// `condition ? when_true : when_false`, evaluating only the taken branch.
+    ConditionalExpression {
+        condition: Rc<ASTNode>,
+        when_true: Rc<ASTNode>,
+        when_false: Rc<ASTNode>
+    },
     VarDeclaration {
-        name: String,
+        pattern: Pattern,
         value: Rc<ASTNode>
     },
     VarAssignment {
@@ -323,9 +693,91 @@ pub enum ASTNode {
         expr: Rc<ASTNode>,
         true_block: Vec<Rc<ASTNode>>,
         false_block: Option<Vec<Rc<ASTNode>>>
+    },
+    /// Loops carry the label written before them, if any, for `break` and
+    /// `continue` to name them.
+    WhileStmt {
+        label: Option<String>,
+        condition: Rc<ASTNode>,
+        body: Vec<Rc<ASTNode>>
+    },
+    DoWhileStmt {
+        label: Option<String>,
+        body: Vec<Rc<ASTNode>>,
+        condition: Rc<ASTNode>
+    },
+    /// A C-style loop. Without a condition it loops until a `return`.
+    ForStmt {
+        label: Option<String>,
+        init: Option<Rc<ASTNode>>,
+        condition: Option<Rc<ASTNode>>,
+        update: Option<Rc<ASTNode>>,
+        body: Vec<Rc<ASTNode>>
+    },
+    /// Runs `try_block` and, if it raises a runtime error, runs `catch_block`
+    /// with the thrown value, or the error message, bound to `error_name`.
+    TryCatch {
+        try_block: Vec<Rc<ASTNode>>,
+        error_name: String,
+        catch_block: Vec<Rc<ASTNode>>
+    },
+    FunctionDeclaration {
+        name: String,
+        params: Vec<Param>,
+        body: Vec<Rc<ASTNode>>
+    },
+    ReturnStmt {
+        value: Option<Rc<ASTNode>>
+    },
+    /// Leaves the innermost loop, or the enclosing loop named `label`.
+    Break {
+        label: Option<String>
+    },
+    /// Skips to the next iteration of the innermost loop, or of the
+    /// enclosing loop named `label`.
+    Continue {
+        label: Option<String>
+    },
+    Throw {
+        value: Rc<ASTNode>
+    }
+}
+
+/// The target of a binding: a plain name, `_` to discard the value or an
+/// array pattern whose elements are bound to the matching elements of an
+/// array value.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Name(String),
+    Ignore,
+    Array(Vec<Pattern>)
+}
+
+impl Pattern {
+    /// The names bound by the pattern, in order.
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Self::Name(name) => vec![name.as_str()],
+            Self::Ignore => vec![],
+            Self::Array(elements) => elements.iter().flat_map(|element| element.names()).collect()
+        }
     }
 }
 
+/// A param of a user-defined function. Params with a default value can be
+/// left out by the caller and must come after every param without one.
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub pattern: Pattern,
+    pub default: Option<Rc<ASTNode>>
+}
+
+/// The range of argument counts accepted by a function declaring `params`.
+pub fn arity(params: &[Param]) -> RangeInclusive<usize> {
+    let required = params.iter().filter(|param| param.default.is_none()).count();
+    required..=params.len()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -335,4 +787,71 @@ mod test {
         let mut p = Parser::new("let x = 4;");
         dbg!(&p.parse());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_expression_statements() {
+        let ast = Parser::new("(1 + 2) * 3; \"a\"; true; -4; [1]; f(1) + 2; x; null;").parse();
+        assert_eq!(ast.len(), 8);
+        assert!(matches!(ast[0].as_ref(), ASTNode::BinaryExpression { operator: "*", .. }));
+        assert!(matches!(ast[1].as_ref(), ASTNode::String(..)));
+        assert!(matches!(ast[2].as_ref(), ASTNode::Bool(true)));
+        assert!(matches!(ast[3].as_ref(), ASTNode::UnaryExpression { sign: '-', .. }));
+        assert!(matches!(ast[4].as_ref(), ASTNode::ArrayLiteral { .. }));
+        assert!(matches!(ast[5].as_ref(), ASTNode::BinaryExpression { operator: "+", .. }));
+        assert!(matches!(ast[6].as_ref(), ASTNode::Identifier { .. }));
+        assert!(matches!(ast[7].as_ref(), ASTNode::Null));
+    }
+
+    #[test]
+    fn test_assignment_and_equality() {
+        let ast = Parser::new("x = 5; x == 5; y = x == 5;").parse();
+        assert!(matches!(ast[0].as_ref(), ASTNode::VarAssignment { name, .. } if name == "x"));
+        assert!(matches!(ast[1].as_ref(), ASTNode::BinaryExpression { operator: "==", .. }));
+        match ast[2].as_ref() {
+            ASTNode::VarAssignment { value, .. } => assert!(matches!(value.as_ref(), ASTNode::BinaryExpression { operator: "==", .. })),
+            node => panic!("Expected an assignment, got {:?}", node)
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Not recognized token")]
+    fn test_unrecognized_statement() {
+        Parser::new("; 1;").parse();
+    }
+
+    #[test]
+    fn test_paren_statements() {
+        let ast = Parser::new("(a + b); (f(x)); ((1 + (2 * 3)));").parse();
+        assert_eq!(ast.len(), 3);
+        assert!(matches!(ast[0].as_ref(), ASTNode::BinaryExpression { operator: "+", .. }));
+        assert!(matches!(ast[1].as_ref(), ASTNode::FunctionCall { name, .. } if name == "f"));
+        match ast[2].as_ref() {
+            ASTNode::BinaryExpression { right, operator: "+", .. } => {
+                assert!(matches!(right.as_ref(), ASTNode::BinaryExpression { operator: "*", .. }));
+            },
+            node => panic!("Expected a sum, got {:?}", node)
+        }
+    }
+
+    #[test]
+    fn test_adjacent_string_literals() {
+        let ast = Parser::new("\"foo\" 'bar'\n    \"baz\"; \"a\" + \"b\";").parse();
+        assert_eq!(ast.len(), 2);
+        assert!(matches!(ast[0].as_ref(), ASTNode::String(value) if value.as_ref() == "foobarbaz"));
+        assert!(matches!(ast[1].as_ref(), ASTNode::BinaryExpression { operator: "+", .. }));
+    }
+
+    #[test]
+    fn test_comments_inside_lists() {
+        let same_ast = |with_comments: &str, without: &str| {
+            assert_eq!(format!("{:?}", Parser::new(with_comments).parse()), format!("{:?}", Parser::new(without).parse()));
+        };
+        same_ast("f(1, /* skip */ 2);", "f(1, 2);");
+        same_ast("f(/* first */ 1 /* after */, 2 // trailing\n);", "f(1, 2);");
+        same_ast("f(// nothing\n);", "f();");
+        same_ast("[/* a */ 1, // b\n 2 /* c */];", "[1, 2];");
+        same_ast("[1, /**/ [2 /* nested */, 3]];", "[1, [2, 3]];");
+        same_ast("f(4 * 2, /* twice */ 1);", "f(4 * 2, 1);");
+        same_ast("fn g(a, /* b */ b) { return a; }", "fn g(a, b) { return a; }");
+    }
+}
@@ -0,0 +1,311 @@
+use std::rc::Rc;
+
+use super::parser::{ASTNode, Param, Parser, Pattern};
+
+const INDENT: &str = "    ";
+
+/// Parses `input` and renders it back in canonical form, so two programs that
+/// only differ in whitespace or redundant parentheses render identically.
+pub fn canonical_source(input: &str) -> String {
+    let ast = Parser::new(input).parse();
+    render_block(&ast, 0)
+}
+
+impl ASTNode {
+    /// Renders the node as source code. Statements end with a semicolon and
+    /// nested blocks are indented by `depth` levels.
+    pub fn to_source(&self, depth: usize) -> String {
+        let indent = INDENT.repeat(depth);
+        match self {
+            Self::VarDeclaration { pattern, value } => format!("{}let {} = {};", indent, render_pattern(pattern), value.to_expr_source()),
+            Self::VarAssignment { .. } => format!("{}{};", indent, self.to_expr_source()),
+            Self::IfStmt { expr, true_block, false_block } => {
+                let mut source = format!("{}if ({}) {{\n{}{}}}", indent, expr.to_expr_source(), render_block_lines(true_block, depth + 1), indent);
+                match false_block.as_deref() {
+                    Some([else_if]) if matches!(else_if.as_ref(), Self::IfStmt { .. }) => {
+                        source.push_str(&format!(" else {}", else_if.to_source(depth).trim_start()));
+                    },
+                    Some(false_block) => {
+                        source.push_str(&format!(" else {{\n{}{}}}", render_block_lines(false_block, depth + 1), indent));
+                    },
+                    None => {}
+                }
+                source
+            },
+            Self::WhileStmt { label, condition, body } => {
+                format!("{}{}while ({}) {{\n{}{}}}", indent, render_label(label), condition.to_expr_source(), render_block_lines(body, depth + 1), indent)
+            },
+            Self::ForStmt { label, init, condition, update, body } => {
+                let init = init.as_ref().map_or(";".to_string(), |init| init.to_source(0));
+                let condition = condition.as_ref().map_or(String::new(), |condition| format!(" {}", condition.to_expr_source()));
+                let update = update.as_ref().map_or(String::new(), |update| format!(" {}", update.to_expr_source()));
+                format!("{}{}for ({}{};{}) {{\n{}{}}}", indent, render_label(label), init, condition, update, render_block_lines(body, depth + 1), indent)
+            },
+            Self::DoWhileStmt { label, body, condition } => {
+                format!("{}{}do {{\n{}{}}} while ({});", indent, render_label(label), render_block_lines(body, depth + 1), indent, condition.to_expr_source())
+            },
+            Self::TryCatch { try_block, error_name, catch_block } => {
+                format!(
+                    "{}try {{\n{}{}}} catch ({}) {{\n{}{}}}",
+                    indent, render_block_lines(try_block, depth + 1), indent, error_name, render_block_lines(catch_block, depth + 1), indent
+                )
+            },
+            Self::FunctionDeclaration { name, params, body } => {
+                format!("{}fn {}({}) {{\n{}{}}}", indent, name, render_params(params), render_block_lines(body, depth + 1), indent)
+            },
+            Self::ReturnStmt { value: Some(value) } => format!("{}return {};", indent, value.to_expr_source()),
+            Self::ReturnStmt { value: None } => format!("{}return;", indent),
+            Self::Throw { value } => format!("{}throw {};", indent, value.to_expr_source()),
+            Self::Break { label } => format!("{}break{};", indent, label.as_ref().map_or(String::new(), |label| format!(" {}", label))),
+            Self::Continue { label } => format!("{}continue{};", indent, label.as_ref().map_or(String::new(), |label| format!(" {}", label))),
+            expression => format!("{}{};", indent, expression.to_expr_source())
+        }
+    }
+
+    fn to_expr_source(&self) -> String {
+        match self {
+            Self::Number(value) if value.is_nan() => "nan".to_string(),
+            Self::Number(value) => value.to_string(),
+            Self::Bool(value) => value.to_string(),
+            Self::Null => "null".to_string(),
+            Self::String(value) => format!("\"{}\"", escape(value)),
+            Self::ArrayLiteral { elements } => format!("[{}]", render_list(elements)),
+            Self::ObjectLiteral { entries } => {
+                let entries: Vec<String> = entries.iter()
+                    .map(|(key, value)| format!("{}: {}", render_key(key), value.to_expr_source()))
+                    .collect();
+                format!("{{{}}}", entries.join(", "))
+            },
+            Self::Identifier { name } => name.to_owned(),
+            Self::VarAssignment { name, value } => format!("{} = {}", name, value.to_expr_source()),
+            Self::FunctionCall { name, args } => format!("{}({})", name, render_list(args)),
+            Self::MethodCall { receiver, name, args } => {
+                format!("{}.{}({})", receiver.to_operand_source(usize::MAX), name, render_list(args))
+            },
+            Self::Index { target, index } => format!("{}[{}]", target.to_operand_source(usize::MAX), index.to_expr_source()),
+            Self::BinaryExpression { left, right, operator } => {
+                let precedence = operator_precedence(operator);
+                format!("{} {} {}", left.to_operand_source(precedence), operator, right.to_operand_source(precedence + 1))
+            },
+            Self::UnaryExpression { sign, expr } => format!("{}{}", sign, expr.to_operand_source(usize::MAX)),
+            Self::Spread { expr } => format!("...{}", expr.to_expr_source()),
+            Self::ConditionalExpression { condition, when_true, when_false } => {
+                format!("{} ? {} : {}", condition.to_operand_source(1), when_true.to_expr_source(), when_false.to_expr_source())
+            },
+            statement => panic!("{:?} cannot be rendered as an expression", statement)
+        }
+    }
+
+    /// Renders an operand, wrapping it in parentheses when it binds looser
+    /// than `min_precedence`.
+    fn to_operand_source(&self, min_precedence: usize) -> String {
+        match self {
+            Self::BinaryExpression { operator, .. } if operator_precedence(operator) < min_precedence => {
+                format!("({})", self.to_expr_source())
+            },
+            Self::UnaryExpression { .. } if min_precedence == usize::MAX => format!("({})", self.to_expr_source()),
+            Self::ConditionalExpression { .. } | Self::VarAssignment { .. } if min_precedence > 0 => format!("({})", self.to_expr_source()),
+            _ => self.to_expr_source()
+        }
+    }
+}
+
+fn operator_precedence(operator: &str) -> usize {
+    match operator {
+        "??" => 1,
+        "||" => 2,
+        "&&" => 3,
+        "==" | "!=" => 4,
+        ">" | "<" | ">=" | "<=" => 5,
+        "+" | "-" => 6,
+        "*" | "/" | "%" => 7,
+        "^" => 8,
+        _ => unreachable!("Unexpected operator")
+    }
+}
+
+fn render_params(params: &[Param]) -> String {
+    params.iter().map(|param| {
+        let pattern = render_patterns(std::slice::from_ref(&param.pattern));
+        match &param.default {
+            Some(default) => format!("{} = {}", pattern, default.to_expr_source()),
+            None => pattern
+        }
+    }).collect::<Vec<String>>().join(", ")
+}
+
+fn render_pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Name(name) => name.to_owned(),
+        Pattern::Ignore => "_".to_string(),
+        Pattern::Array(elements) => format!("[{}]", render_patterns(elements))
+    }
+}
+
+fn render_patterns(patterns: &[Pattern]) -> String {
+    patterns.iter().map(render_pattern).collect::<Vec<String>>().join(", ")
+}
+
+fn render_label(label: &Option<String>) -> String {
+    label.as_ref().map_or(String::new(), |label| format!("{}: ", label))
+}
+
+fn render_key(key: &str) -> String {
+    let is_identifier = key.chars().next().is_some_and(|char| char.is_ascii_alphabetic())
+        && key.chars().all(|char| char.is_ascii_alphanumeric() || char == '_');
+    if is_identifier {
+        key.to_owned()
+    } else {
+        format!("\"{}\"", escape(key))
+    }
+}
+
+/// Escapes `value` so it can be written between double quotes in a script.
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for char in value.chars() {
+        match char {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            char => escaped.push(char)
+        }
+    }
+    escaped
+}
+
+fn render_list(nodes: &[Rc<ASTNode>]) -> String {
+    nodes.iter().map(|node| node.to_expr_source()).collect::<Vec<String>>().join(", ")
+}
+
+fn render_block(nodes: &[Rc<ASTNode>], depth: usize) -> String {
+    nodes.iter().map(|node| node.to_source(depth)).collect::<Vec<String>>().join("\n")
+}
+
+fn render_block_lines(nodes: &[Rc<ASTNode>], depth: usize) -> String {
+    nodes.iter().map(|node| format!("{}\n", node.to_source(depth))).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_canonical_source_ignores_whitespace() {
+        assert_eq!(canonical_source("let x=1;"), canonical_source("let   x  =  1 ;"));
+        assert_eq!(canonical_source("let x=1;"), "let x = 1;");
+    }
+
+    #[test]
+    fn test_canonical_source_statements() {
+        let compact = "let y=(1+2)*3;fn add(a,b){y=a+b;}if(y>2){print(add(y,-y));}";
+        let spread = r#"
+            let y = (1 + 2) * 3;
+            fn add(a, b) {
+                y = a + b;
+            }
+            if (y > 2) {
+                print(add(y, -y));
+            }
+        "#;
+        assert_eq!(canonical_source(compact), canonical_source(spread));
+        assert_eq!(
+            canonical_source(compact),
+            "let y = (1 + 2) * 3;\nfn add(a, b) {\n    y = a + b;\n}\nif (y > 2) {\n    print(add(y, -y));\n}"
+        );
+    }
+
+    #[test]
+    fn test_canonical_source_drops_redundant_parens() {
+        assert_eq!(canonical_source("let z = (1 * 2) + (3);"), "let z = 1 * 2 + 3;");
+        assert_eq!(canonical_source("let z = 1 - (2 - 3);"), "let z = 1 - (2 - 3);");
+    }
+
+    #[test]
+    fn test_canonical_source_do_while() {
+        assert_eq!(canonical_source("do{x=x+1;}while(x<3)"), "do {\n    x = x + 1;\n} while (x < 3);");
+    }
+
+    #[test]
+    fn test_canonical_source_pattern_params() {
+        assert_eq!(canonical_source("fn f([a,[b,c]],d){return a;}"), "fn f([a, [b, c]], d) {\n    return a;\n}");
+    }
+
+    #[test]
+    fn test_canonical_source_object_literal() {
+        assert_eq!(canonical_source("let o={a:1,'b c':[2],};"), "let o = {a: 1, \"b c\": [2]};");
+    }
+
+    #[test]
+    fn test_canonical_source_escapes() {
+        let source = canonical_source(r#"let s = 'say "hi"\n\t\\';"#);
+        assert_eq!(source, r#"let s = "say \"hi\"\n\t\\";"#);
+        assert_eq!(canonical_source(&source), source);
+    }
+
+    #[test]
+    fn test_canonical_source_conditional() {
+        assert_eq!(canonical_source("let x=a??b==c?1:(d?2:3)+1;"), "let x = a ?? b == c ? 1 : (d ? 2 : 3) + 1;");
+    }
+
+    #[test]
+    fn test_canonical_source_try_catch() {
+        assert_eq!(canonical_source("try{x=1/0;}catch(e){print(e);}"), "try {\n    x = 1 / 0;\n} catch (e) {\n    print(e);\n}");
+    }
+
+    #[test]
+    fn test_canonical_source_destructuring() {
+        assert_eq!(canonical_source("let [_,[a,b]]=pair;fn f([_,x]){x}"), "let [_, [a, b]] = pair;\nfn f([_, x]) {\n    x;\n}");
+    }
+
+    #[test]
+    fn test_canonical_source_chained_assignment() {
+        assert_eq!(canonical_source("a=b=c+1;"), "a = b = c + 1;");
+    }
+
+    #[test]
+    fn test_canonical_source_assignment_expression() {
+        assert_eq!(canonical_source("print(x=5);(x=2)+1;"), "print(x = 5);\n(x = 2) + 1;");
+    }
+
+    #[test]
+    fn test_canonical_source_else_if() {
+        let source = canonical_source("if(a){1;}else if(b){2;}else{3;}");
+        assert_eq!(source, "if (a) {\n    1;\n} else if (b) {\n    2;\n} else {\n    3;\n}");
+        assert_eq!(canonical_source(&source), source);
+    }
+
+    #[test]
+    fn test_canonical_source_while() {
+        assert_eq!(canonical_source("while(x<3){x=x+1;}"), "while (x < 3) {\n    x = x + 1;\n}");
+    }
+
+    #[test]
+    fn test_canonical_source_for() {
+        let source = canonical_source("for(let i=0;i<3;i=i+1){print(i);}");
+        assert_eq!(source, "for (let i = 0; i < 3; i = i + 1) {\n    print(i);\n}");
+        assert_eq!(canonical_source(&source), source);
+        assert_eq!(canonical_source("for(;;){}"), "for (;;) {\n}");
+    }
+
+    #[test]
+    fn test_canonical_source_logical_operators() {
+        assert_eq!(canonical_source("(a||b)&&c==d||e;"), "(a || b) && c == d || e;");
+    }
+
+    #[test]
+    fn test_canonical_source_index() {
+        assert_eq!(canonical_source("a[1]+m[i][j-1];"), "a[1] + m[i][j - 1];");
+        assert_eq!(canonical_source("(a+b)[0];"), "(a + b)[0];");
+    }
+
+    #[test]
+    fn test_canonical_source_labeled_loops() {
+        let source = "outer: while (true) { for (;;) { break outer; } continue; }";
+        assert_eq!(canonical_source(source), "outer: while (true) {\n    for (;;) {\n        break outer;\n    }\n    continue;\n}");
+        assert_eq!(canonical_source("l: do { break } while (true)"), "l: do {\n    break;\n} while (true);");
+    }
+}
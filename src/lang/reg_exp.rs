@@ -1,11 +1,6 @@
-use regex::Regex;
-
-macro_rules! check_regex {
-    ($pattern: expr, $value: expr) => {{
-        Regex::new($pattern).unwrap().is_match($value)
-    }};
-}
-
+/// The character classes the tokenizer tells apart. Every class is a plain
+/// set of characters, so they are tested with `char` comparisons instead of
+/// compiling a regular expression on each check.
 pub enum TokenRegEx {
     EmptySpace,
     Char,
@@ -15,15 +10,9 @@ pub enum TokenRegEx {
     SemiColon,
     LeftParen,
     RightParen,
-    EqOp,
     AddOp,
     SubOp,
     DivOp,
-    MulOp,
-    PowOp,
-    NegationOp,
-    GtOp,
-    LtOp,
     LeftSqBrace,
     RightSqBrace,
     LeftCurlyBrace,
@@ -33,31 +22,30 @@ pub enum TokenRegEx {
 }
 
 impl TokenRegEx {
+    /// Whether any character of `value` belongs to the class.
     pub fn test(&self, value: &str) -> bool {
+        value.chars().any(|char| self.matches(char))
+    }
+
+    pub fn matches(&self, char: char) -> bool {
         match self {
-            TokenRegEx::EmptySpace => check_regex!(r"[\s\t\n\r]", value),
-            TokenRegEx::Char => check_regex!(r"[a-zA-Z]", value),
-            TokenRegEx::Number => check_regex!(r"[0-9]", value),
-            TokenRegEx::SimpleQuote => check_regex!(r"[']", value),
-            TokenRegEx::DoubleQuote => check_regex!(r#"["]"#, value),
-            TokenRegEx::SemiColon => check_regex!(r"[;]", value),
-            TokenRegEx::LeftParen => check_regex!(r"[(]", value),
-            TokenRegEx::RightParen => check_regex!(r"[)]", value),
-            TokenRegEx::EqOp => check_regex!(r"[\=]", value),
-            TokenRegEx::AddOp => check_regex!(r"[\+]", value),
-            TokenRegEx::SubOp => check_regex!(r"[\-]", value),
-            TokenRegEx::DivOp => check_regex!(r"[\/]", value),
-            TokenRegEx::MulOp => check_regex!(r"[\*]", value),
-            TokenRegEx::PowOp => check_regex!(r"[\^]", value),
-            TokenRegEx::GtOp => check_regex!(r"[>]", value),
-            TokenRegEx::LtOp => check_regex!(r"[<]", value),
-            TokenRegEx::NegationOp => check_regex!(r"[\!]", value),
-            TokenRegEx::LeftSqBrace => check_regex!(r"[\[]", value),
-            TokenRegEx::RightSqBrace => check_regex!(r"[\]]", value),
-            TokenRegEx::LeftCurlyBrace => check_regex!(r"[\{]", value),
-            TokenRegEx::RightCurlyBrace => check_regex!(r"[\}]", value),
-            TokenRegEx::Comma => check_regex!(r"[\,]", value),
-            TokenRegEx::DecimalPoint => check_regex!(r"[\.]", value),
+            TokenRegEx::EmptySpace => char.is_whitespace(),
+            TokenRegEx::Char => char.is_ascii_alphabetic() || char == '_',
+            TokenRegEx::Number => char.is_ascii_digit(),
+            TokenRegEx::SimpleQuote => char == '\'',
+            TokenRegEx::DoubleQuote => char == '"',
+            TokenRegEx::SemiColon => char == ';',
+            TokenRegEx::LeftParen => char == '(',
+            TokenRegEx::RightParen => char == ')',
+            TokenRegEx::AddOp => char == '+',
+            TokenRegEx::SubOp => char == '-',
+            TokenRegEx::DivOp => char == '/',
+            TokenRegEx::LeftSqBrace => char == '[',
+            TokenRegEx::RightSqBrace => char == ']',
+            TokenRegEx::LeftCurlyBrace => char == '{',
+            TokenRegEx::RightCurlyBrace => char == '}',
+            TokenRegEx::Comma => char == ',',
+            TokenRegEx::DecimalPoint => char == '.',
         }
     }
 }
@@ -73,10 +61,20 @@ mod test {
         let new_line = TokenRegEx::EmptySpace.test("\n");
         let character = TokenRegEx::EmptySpace.test("hello");
         let decimal_point = TokenRegEx::DecimalPoint.test(".");
-        assert_eq!(space, true);
-        assert_eq!(tab, true);
-        assert_eq!(new_line, true);
-        assert_eq!(character, false);
-        assert_eq!(decimal_point, true);
+        assert!(space);
+        assert!(tab);
+        assert!(new_line);
+        assert!(!character);
+        assert!(decimal_point);
+    }
+
+    #[test]
+    fn test_reg_ex_char_classes() {
+        assert!(TokenRegEx::Char.test("_"));
+        assert!(!TokenRegEx::Char.test("1"));
+        assert!(!TokenRegEx::Char.test("é"));
+        assert!(TokenRegEx::Number.test("7"));
+        assert!(TokenRegEx::EmptySpace.test("\u{a0}"));
+        assert!(!TokenRegEx::Comma.test(""));
     }
 }
\ No newline at end of file
@@ -0,0 +1,137 @@
+use std::{fs, io::{self, BufRead, Write}};
+
+use super::interpreter::{Interpreter, RuntimeValue};
+
+/// An interactive session over an interpreter. Lines starting with `.` are
+/// commands for the session itself and are never run as script:
+///
+/// - `.help` lists the functions that can be called
+/// - `.vars` lists the declared variables and their values
+/// - `.clear` drops every variable and script-defined function
+/// - `.load <file>` runs a file into the session
+/// - `.exit` ends the session
+pub struct Repl {
+    interpreter: Interpreter
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new(Interpreter::new())
+    }
+}
+
+impl Repl {
+    pub fn new(interpreter: Interpreter) -> Self {
+        Self { interpreter }
+    }
+
+    /// Handles one line of input and returns the text to show for it, which is
+    /// empty for statements without a value. Returns `None` after `.exit`.
+    pub fn eval_line(&mut self, line: &str) -> Option<String> {
+        let line = line.trim();
+        let Some(command) = line.strip_prefix('.') else {
+            return Some(self.run_source(line));
+        };
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command, "")
+        };
+        let output = match name {
+            "exit" => return None,
+            "help" => format!("Functions: {}", self.interpreter.function_names().join(", ")),
+            "vars" => self.list_variables(),
+            "clear" => {
+                self.interpreter.reset();
+                "Session cleared".to_string()
+            },
+            "load" if argument.is_empty() => "Usage: .load <file>".to_string(),
+            "load" => match fs::read_to_string(argument) {
+                Ok(source) => self.run_source(&source),
+                Err(error) => format!("Cannot read {}: {}", argument, error)
+            },
+            _ => format!("Unknown command .{}, try .help", name)
+        };
+        Some(output)
+    }
+
+    /// Reads lines from `input` until it ends or `.exit` is entered, writing
+    /// a prompt before each line and the result after it.
+    pub fn run(&mut self, input: impl BufRead, output: &mut impl Write) -> io::Result<()> {
+        write!(output, "> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let Some(result) = self.eval_line(&line?) else {
+                break;
+            };
+            if !result.is_empty() {
+                writeln!(output, "{}", result)?;
+            }
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    fn run_source(&mut self, source: &str) -> String {
+        if source.is_empty() {
+            return String::new();
+        }
+        match self.interpreter.run_checked(source) {
+            Ok(RuntimeValue::Null) => String::new(),
+            Ok(value) => value.to_string(),
+            Err(error) => format!("Error: {}", error.report(source))
+        }
+    }
+
+    fn list_variables(&self) -> String {
+        let names = self.interpreter.variable_names();
+        if names.is_empty() {
+            return "No variables".to_string();
+        }
+        names.iter()
+            .filter_map(|name| self.interpreter.variable(name).map(|value| format!("{} = {}", name, value)))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_repl_commands() {
+        let mut repl = Repl::default();
+        assert_eq!(repl.eval_line(".vars"), Some("No variables".to_string()));
+        assert_eq!(repl.eval_line("let x = 5;"), Some(String::new()));
+        assert_eq!(repl.eval_line("let name = \"ada\";"), Some(String::new()));
+        assert_eq!(repl.eval_line(".vars"), Some("name = ada\nx = 5".to_string()));
+        assert_eq!(repl.eval_line("x * 2"), Some("10".to_string()));
+        assert_eq!(repl.eval_line("1 / 0;"), Some("Error: Cannot divide by zero: 1 / 0".to_string()));
+        repl.eval_line("fn twice(n) { n * 2 }");
+        let help = repl.eval_line(".help").unwrap();
+        assert!(help.starts_with("Functions: ") && help.contains("twice") && help.contains("print"));
+        assert_eq!(repl.eval_line(".clear"), Some("Session cleared".to_string()));
+        assert_eq!(repl.eval_line(".vars"), Some("No variables".to_string()));
+        assert_eq!(repl.eval_line(".nope"), Some("Unknown command .nope, try .help".to_string()));
+        assert_eq!(repl.eval_line(".exit"), None);
+    }
+
+    #[test]
+    fn test_repl_syntax_error_context() {
+        let mut repl = Repl::default();
+        assert_eq!(repl.eval_line("let a = 1 # 2;"), Some("Error: Unexpected token '#' at position 10 (line 1, column 11)\nlet a = 1 # 2;\n          ^".to_string()));
+    }
+
+    #[test]
+    fn test_repl_load_and_run() {
+        let path = std::env::temp_dir().join("repl_load_test.rs_script");
+        fs::write(&path, "let loaded = 3;\nfn triple(n) { n * loaded }").unwrap();
+        let input = format!(".load {}\ntriple(2)\n.exit\nnever_run()\n", path.display());
+        let mut output = vec![];
+        Repl::default().run(input.as_bytes(), &mut output).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "> > 6\n> ");
+        assert!(Repl::default().eval_line(".load /missing/file").unwrap().starts_with("Cannot read /missing/file"));
+    }
+}
@@ -1,25 +1,38 @@
-use super::{panics::{casting_error, unexpected_eof, unexpected_token}, reg_exp::TokenRegEx};
+use super::{panics::{casting_error, invalid_number, unexpected_character, unexpected_eof, unknown_escape}, reg_exp::TokenRegEx};
 
-pub struct Tokenizer<'a> {
+pub struct Tokenizer {
     pos: u32,
-    text: &'a str,
-    tokens: Vec<Token>
+    chars: Vec<char>,
+    tokens: Vec<Token>,
+    keep_comments: bool
 }
 
-impl<'a> Tokenizer<'a> {
-    pub fn new(text: &'a str) -> Self {
-        Tokenizer { pos: 0, text: text.trim(), tokens: vec![] }
+impl Tokenizer {
+    /// Comments are dropped unless `keep_comments` is set, in which case they
+    /// are emitted as `Token::Comment` so a formatter can preserve them.
+    pub fn new(text: &str, keep_comments: bool) -> Self {
+        Tokenizer { pos: 0, chars: text.chars().collect(), tokens: vec![], keep_comments }
     }
 
     pub fn tokenize(&mut self) -> Vec<Token> {
         while !self.is_eof() {
             self.skip_empty_space();
+            if self.is_eof() {
+                break;
+            }
             let current = self.current();
             
             if self.is_char(&current) {
                 let identifier = self.identifier();
                 if identifier == "true" || identifier == "false" {
                     self.tokens.push(Token::BoolLiteral { value: identifier == "true" });
+                } else if identifier == "null" {
+                    self.tokens.push(Token::NullLiteral);
+                } else if identifier == "inf" {
+                    self.tokens.push(Token::NumberLiteral { value: f32::INFINITY });
+                } else if identifier == "nan" {
+                    // nan is unordered: every <, > comparison involving it is false
+                    self.tokens.push(Token::NumberLiteral { value: f32::NAN });
                 } else {
                     self.tokens.push(Token::Identifier { value: identifier });
                 }
@@ -32,6 +45,18 @@ impl<'a> Tokenizer<'a> {
                 continue;
             }
 
+            if self.is_decimal_point(&current) && self.is_spread() {
+                self.pos += 3;
+                self.tokens.push(Token::Spread);
+                continue;
+            }
+
+            if self.is_decimal_point(&current) {
+                self.advance();
+                self.tokens.push(Token::Dot);
+                continue;
+            }
+
             if self.is_simple_quote(&current) || self.is_double_quote(&current) {
                 let value = self.string(&current);
                 self.tokens.push(Token::StringLiteral { value });
@@ -82,62 +107,18 @@ impl<'a> Tokenizer<'a> {
 
             //Operators
 
-            if self.is_eq_op(&current) {
-                self.advance();
-                self.tokens.push(Token::EqOp);
-                continue;
-            }
-
-            if self.is_negation_op(&current) {
-                self.advance();
-                if self.is_eq_op(&self.current()) {
-                    self.advance();
-                    self.tokens.push(Token::NotEqOp);
-                    continue;
+            if self.is_div_op(&current) && self.is_comment_start() {
+                let start = self.pos;
+                let text = self.comment();
+                if self.keep_comments {
+                    self.tokens.push(Token::Comment { text, span: Span { start, end: self.pos } });
                 }
-                self.tokens.push(Token::NegationOp);
-                continue;
-            }
-
-            if self.is_add_op(&current) {
-                self.advance();
-                self.tokens.push(Token::AddOp);
-                continue;
-            }
-
-            if self.is_sub_op(&current) {
-                self.advance();
-                self.tokens.push(Token::SubOp);
-                continue;
-            }
-
-            if self.is_mul_op(&current) {
-                self.advance();
-                self.tokens.push(Token::MulOp);
-                continue;
-            }
-
-            if self.is_div_op(&current) {
-                self.advance();
-                self.tokens.push(Token::DivOp);
-                continue;
-            }
-
-            if self.is_pow_op(&current) {
-                self.advance();
-                self.tokens.push(Token::PowOp);
-                continue;
-            }
-
-            if self.is_gt_op(&current) {
-                self.advance();
-                self.tokens.push(Token::GtOp);
                 continue;
             }
 
-            if self.is_lt_op(&current) {
-                self.advance();
-                self.tokens.push(Token::LtOp);
+            if let Some((lexeme, token)) = self.operator() {
+                self.pos += lexeme.chars().count() as u32;
+                self.tokens.push(token.clone());
                 continue;
             }
 
@@ -147,14 +128,15 @@ impl<'a> Tokenizer<'a> {
                 continue;
             }
 
-            unexpected_token(&current, &self.pos);
+            // a single & or | is not an operator and ends up here
+            unexpected_character(&current, &self.pos);
         }
         self.tokens.push(Token::EOF);
         self.tokens.clone()
     }
     
     fn skip_empty_space(&mut self) {
-        while self.is_empty_space(&self.current()) {
+        while !self.is_eof() && self.is_empty_space(&self.current()) {
             self.advance();
         }
     }
@@ -164,14 +146,14 @@ impl<'a> Tokenizer<'a> {
     }
     
     fn current(&self) -> String {
-        match self.text.chars().nth(self.pos as usize) {
+        match self.chars.get(self.pos as usize) {
             Some(value) => value.to_string(),
             None => unexpected_eof(&self.pos),
         }
     }
 
     fn next(&self) -> String {
-        match self.text.chars().nth((self.pos + 1) as usize) {
+        match self.chars.get((self.pos + 1) as usize) {
             Some(value) => value.to_string(),
             None => unexpected_eof(&self.pos)
         }
@@ -195,7 +177,7 @@ impl<'a> Tokenizer<'a> {
         let mut is_there_decimal_point = false;
         while !self.is_eof() && (self.is_number(&self.current()) || self.is_decimal_point(&self.current())) {
             if is_there_decimal_point && self.is_decimal_point(&self.current()) {
-                panic!("Invalid format number at position {}", self.pos);
+                invalid_number(&self.pos);
             }
             if !is_there_decimal_point {
                 is_there_decimal_point = self.is_decimal_point(&self.current());
@@ -203,9 +185,53 @@ impl<'a> Tokenizer<'a> {
             value.push_str(&self.current());
             self.advance();
         }
+        if self.is_exponent_start() {
+            value.push_str(&self.current());
+            self.advance();
+            if self.is_add_op(&self.current()) || self.is_sub_op(&self.current()) {
+                value.push_str(&self.current());
+                self.advance();
+            }
+            while !self.is_eof() && self.is_number(&self.current()) {
+                value.push_str(&self.current());
+                self.advance();
+            }
+        }
         value.parse::<f32>().unwrap()
     }
 
+    /// Checks for an exponent like `e10`, `E-3` or `e+2` following a number.
+    fn is_exponent_start(&self) -> bool {
+        let exponent = self.peek(0);
+        if exponent != Some("e".to_string()) && exponent != Some("E".to_string()) {
+            return false;
+        }
+        match self.peek(1) {
+            Some(sign) if self.is_add_op(&sign) || self.is_sub_op(&sign) => {
+                self.peek(2).is_some_and(|digit| self.is_number(&digit))
+            },
+            Some(digit) => self.is_number(&digit),
+            None => false
+        }
+    }
+
+    /// The longest entry of `OPERATORS` starting at the current position.
+    fn operator(&self) -> Option<&'static (&'static str, Token)> {
+        let rest = &self.chars[self.pos as usize..];
+        OPERATORS.iter().find(|(lexeme, _)| {
+            let mut chars = rest.iter();
+            lexeme.chars().all(|char| chars.next() == Some(&char))
+        })
+    }
+
+    fn is_spread(&self) -> bool {
+        (0..3).all(|offset| self.peek(offset).is_some_and(|value| self.is_decimal_point(&value)))
+    }
+
+    fn peek(&self, offset: u32) -> Option<String> {
+        self.chars.get((self.pos + offset) as usize).map(|value| value.to_string())
+    }
+
     fn string(&mut self, quote_type: &str) -> String {
         self.advance();
         let mut value = String::new();
@@ -213,6 +239,10 @@ impl<'a> Tokenizer<'a> {
             if self.is_eof() {
                 unexpected_eof(&self.pos);
             }
+            if self.current() == "\\" {
+                self.escape_sequence(&mut value);
+                continue;
+            }
             value.push_str(&self.current());
             self.advance();
         }
@@ -220,8 +250,54 @@ impl<'a> Tokenizer<'a> {
         value
     }
 
+    /// Consumes a backslash and the character after it, pushing what the
+    /// sequence stands for. A backslash at the end of a line joins the next
+    /// line, dropping the line break.
+    fn escape_sequence(&mut self, value: &mut String) {
+        let pos = self.pos;
+        self.advance();
+        if self.is_eof() {
+            unexpected_eof(&self.pos);
+        }
+        match self.current().as_str() {
+            "\n" => {},
+            "\r" if self.peek(1) == Some("\n".to_string()) => self.advance(),
+            sequence => match sequence.chars().next().and_then(escaped_char) {
+                Some(char) => value.push(char),
+                None => unknown_escape(sequence, &pos)
+            }
+        }
+        self.advance();
+    }
+
+    fn is_comment_start(&self) -> bool {
+        matches!(self.peek(1).as_deref(), Some("/") | Some("*"))
+    }
+
+    /// Consumes a `// line` or `/* block */` comment, returning its full text.
+    fn comment(&mut self) -> String {
+        let start = self.pos as usize;
+        self.advance();
+        if self.current() == "/" {
+            while !self.is_eof() && self.current() != "\n" {
+                self.advance();
+            }
+        } else {
+            self.advance();
+            while self.peek(0).as_deref() != Some("*") || self.peek(1).as_deref() != Some("/") {
+                if self.is_eof() {
+                    unexpected_eof(&self.pos);
+                }
+                self.advance();
+            }
+            self.advance();
+            self.advance();
+        }
+        self.chars[start..self.pos as usize].iter().collect()
+    }
+
     fn is_eof(&self) -> bool {
-        self.pos >= self.text.len().try_into().unwrap()
+        self.pos as usize >= self.chars.len()
     }
 
     fn is_char(&self, value: &str) -> bool {
@@ -252,10 +328,6 @@ impl<'a> Tokenizer<'a> {
         TokenRegEx::RightParen.test(value)
     }
 
-    fn is_eq_op(&self, value: &str) -> bool {
-        TokenRegEx::EqOp.test(value)
-    }
-
     fn is_add_op(&self, value: &str) -> bool {
         TokenRegEx::AddOp.test(value)
     }
@@ -264,30 +336,10 @@ impl<'a> Tokenizer<'a> {
         TokenRegEx::SubOp.test(value)
     }
     
-    fn is_mul_op(&self, value: &str) -> bool {
-        TokenRegEx::MulOp.test(value)
-    }
-    
     fn is_div_op(&self, value: &str) -> bool {
         TokenRegEx::DivOp.test(value)
     }
     
-    fn is_pow_op(&self, value: &str) -> bool {
-        TokenRegEx::PowOp.test(value)
-    }
-
-    fn is_gt_op(&self, value: &str) -> bool {
-        TokenRegEx::GtOp.test(value)
-    }
-
-    fn is_lt_op(&self, value: &str) -> bool {
-        TokenRegEx::LtOp.test(value)
-    }
-
-    fn is_negation_op(&self, value: &str) -> bool {
-        TokenRegEx::NegationOp.test(value)
-    }
-
     fn is_left_sq_brace(&self, value: &str) -> bool {
         TokenRegEx::LeftSqBrace.test(value)
     }
@@ -314,6 +366,76 @@ impl<'a> Tokenizer<'a> {
 
 }
 
+/// Every operator with its token. Entries are tried in order, so a lexeme
+/// must come before any shorter one it starts with (`==` before `=`).
+static OPERATORS: [(&str, Token); 19] = [
+    ("==", Token::EqEqOp),
+    ("!=", Token::NotEqOp),
+    (">=", Token::GtEqOp),
+    ("<=", Token::LtEqOp),
+    ("&&", Token::AndOp),
+    ("||", Token::OrOp),
+    ("??", Token::NullishOp),
+    ("=", Token::EqOp),
+    ("!", Token::NegationOp),
+    ("+", Token::AddOp),
+    ("-", Token::SubOp),
+    ("*", Token::MulOp),
+    ("/", Token::DivOp),
+    ("%", Token::ModOp),
+    ("^", Token::PowOp),
+    (">", Token::GtOp),
+    ("<", Token::LtOp),
+    ("?", Token::QuestionMark),
+    (":", Token::Colon),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub start: u32,
+    pub end: u32
+}
+
+/// The character the escape sequence `\char` stands for.
+fn escaped_char(char: char) -> Option<char> {
+    match char {
+        'n' => Some('\n'),
+        't' => Some('\t'),
+        'r' => Some('\r'),
+        '0' => Some('\0'),
+        '\\' => Some('\\'),
+        '"' => Some('"'),
+        '\'' => Some('\''),
+        _ => None
+    }
+}
+
+/// Interprets the escape sequences of `value` the way string literals do,
+/// failing on an unknown sequence. The reverse of `printer::escape`.
+pub fn unescape(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let mut unescaped = String::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        if chars[pos] != '\\' {
+            unescaped.push(chars[pos]);
+            pos += 1;
+            continue;
+        }
+        match chars.get(pos + 1) {
+            None => unexpected_eof(&(pos as u32 + 1)),
+            Some('\n') => {},
+            Some('\r') if chars.get(pos + 2) == Some(&'\n') => pos += 1,
+            Some(sequence) => match escaped_char(*sequence) {
+                Some(char) => unescaped.push(char),
+                None => unknown_escape(&sequence.to_string(), &(pos as u32))
+            }
+        }
+        pos += 2;
+    }
+    unescaped
+}
+
 #[derive(Debug, Clone)]
 pub enum Token {
     Identifier {
@@ -325,6 +447,7 @@ pub enum Token {
     BoolLiteral {
         value: bool
     },
+    NullLiteral,
     StringLiteral {
         value: String
     },
@@ -332,20 +455,35 @@ pub enum Token {
     RightParen,
     SemiColon,
     EqOp,
+    EqEqOp,
     NotEqOp,
+    AndOp,
+    OrOp,
     SubOp,
     AddOp,
     MulOp,
+    ModOp,
     DivOp,
     PowOp,
     GtOp,
     LtOp,
+    GtEqOp,
+    LtEqOp,
     NegationOp,
     LeftSqBrace,
     RightSqBrace,
     LeftCurlyBrace,
     RightCurlyBrace,
     Comma,
+    Colon,
+    QuestionMark,
+    NullishOp,
+    Dot,
+    Spread,
+    Comment {
+        text: String,
+        span: Span
+    },
     EOF
 }
 
@@ -355,25 +493,38 @@ impl Token {
             Self::Identifier {..} => TokenKind::Identifier,
             Self::NumberLiteral { .. } => TokenKind::NumberLiteral,
             Self::BoolLiteral { .. } => TokenKind::BoolLiteral,
+            Self::NullLiteral => TokenKind::NullLiteral,
             Self::StringLiteral { .. } => TokenKind::StringLiteral,
             Self::LeftParen => TokenKind::LeftParen,
             Self::RightParen => TokenKind::RightParen,
             Self::SemiColon => TokenKind::SemiColon,
             Self::EqOp => TokenKind::EqOp,
+            Self::EqEqOp => TokenKind::EqEqOp,
             Self::NotEqOp => TokenKind::NotEqOp,
+            Self::AndOp => TokenKind::AndOp,
+            Self::OrOp => TokenKind::OrOp,
             Self::SubOp => TokenKind::SubOp,
             Self::AddOp => TokenKind::AddOp,
             Self::MulOp => TokenKind::MulOp,
+            Self::ModOp => TokenKind::ModOp,
             Self::DivOp => TokenKind::DivOp,
             Self::PowOp => TokenKind::PowOp,
             Self::GtOp => TokenKind::GtOp,
             Self::LtOp => TokenKind::LtOp,
+            Self::GtEqOp => TokenKind::GtEqOp,
+            Self::LtEqOp => TokenKind::LtEqOp,
             Self::NegationOp => TokenKind::NegationOp,
             Self::LeftSqBrace => TokenKind::LeftSqBrace,
             Self::RightSqBrace => TokenKind::RightSqBrace,
             Self::LeftCurlyBrace => TokenKind::LeftCurlyBrace,
             Self::RightCurlyBrace => TokenKind::RightCurlyBrace,
             Self::Comma => TokenKind::Comma,
+            Self::Colon => TokenKind::Colon,
+            Self::QuestionMark => TokenKind::QuestionMark,
+            Self::NullishOp => TokenKind::NullishOp,
+            Self::Dot => TokenKind::Dot,
+            Self::Spread => TokenKind::Spread,
+            Self::Comment { .. } => TokenKind::Comment,
             Self::EOF => TokenKind::EOF,
         }
     }
@@ -405,24 +556,236 @@ pub enum TokenKind {
     Identifier,
     NumberLiteral,
     BoolLiteral,
+    NullLiteral,
     StringLiteral,
     LeftParen,
     RightParen,
     SemiColon,
     EqOp,
+    EqEqOp,
     NotEqOp,
+    AndOp,
+    OrOp,
     SubOp,
     AddOp,
     MulOp,
+    ModOp,
     DivOp,
     PowOp,
     GtOp,
     LtOp,
+    GtEqOp,
+    LtEqOp,
     NegationOp,
     LeftSqBrace,
     RightSqBrace,
     LeftCurlyBrace,
     RightCurlyBrace,
     Comma,
+    Colon,
+    QuestionMark,
+    NullishOp,
+    Dot,
+    Spread,
+    Comment,
     EOF
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lang::{error::{line_column, RuntimeError}, panics::catch};
+
+    use super::*;
+
+    fn tokenize_error(text: &str) -> String {
+        match catch(|| Tokenizer::new(text, false).tokenize()) {
+            Err(RuntimeError::Syntax { message, .. }) => message,
+            result => panic!("Expected a tokenizer error, got {:?}", result)
+        }
+    }
+
+    #[test]
+    fn test_stray_backslash() {
+        assert_eq!(
+            tokenize_error(r"let x = \n;"),
+            r"Unexpected token '\' at position 8: escape sequences are only valid inside strings"
+        );
+    }
+
+    #[test]
+    fn test_operator_hints() {
+        assert_eq!(tokenize_error("a & b"), "Unexpected token '&' at position 2: did you mean '&&'?");
+        assert_eq!(tokenize_error("a | b"), "Unexpected token '|' at position 2: did you mean '||'?");
+        assert_eq!(tokenize_error("a @ b"), "Unexpected token '@' at position 2");
+    }
+
+    fn kinds(tokens: &[Token]) -> Vec<TokenKind> {
+        tokens.iter().map(|token| token.kind()).collect()
+    }
+
+    #[test]
+    fn test_comments_are_dropped() {
+        let tokens = Tokenizer::new("let x = 4 / 2; // half\n/* block\n comment */ x;", false).tokenize();
+        assert_eq!(kinds(&tokens), vec![
+            TokenKind::Identifier, TokenKind::Identifier, TokenKind::EqOp, TokenKind::NumberLiteral,
+            TokenKind::DivOp, TokenKind::NumberLiteral, TokenKind::SemiColon,
+            TokenKind::Identifier, TokenKind::SemiColon, TokenKind::EOF
+        ]);
+    }
+
+    #[test]
+    fn test_comments_are_kept() {
+        let tokens = Tokenizer::new("x; // half\n/* block */ y;", true).tokenize();
+        assert_eq!(kinds(&tokens), vec![
+            TokenKind::Identifier, TokenKind::SemiColon, TokenKind::Comment,
+            TokenKind::Comment, TokenKind::Identifier, TokenKind::SemiColon, TokenKind::EOF
+        ]);
+        match (&tokens[2], &tokens[3]) {
+            (Token::Comment { text: line, span: line_span }, Token::Comment { text: block, span: block_span }) => {
+                assert_eq!(line, "// half");
+                assert_eq!(*line_span, Span { start: 3, end: 10 });
+                assert_eq!(block, "/* block */");
+                assert_eq!(*block_span, Span { start: 11, end: 22 });
+            },
+            tokens => panic!("Expected two comments, got {:?}", tokens)
+        }
+    }
+
+    #[test]
+    fn test_comparison_operators() {
+        let tokens = Tokenizer::new("a >= b <= c > d < e", false).tokenize();
+        assert_eq!(kinds(&tokens), vec![
+            TokenKind::Identifier, TokenKind::GtEqOp, TokenKind::Identifier, TokenKind::LtEqOp,
+            TokenKind::Identifier, TokenKind::GtOp, TokenKind::Identifier, TokenKind::LtOp,
+            TokenKind::Identifier, TokenKind::EOF
+        ]);
+    }
+
+    #[test]
+    fn test_assignment_and_equality() {
+        let tokens = Tokenizer::new("x = 5; x == 5; x != 5; x === 5", false).tokenize();
+        assert_eq!(kinds(&tokens), vec![
+            TokenKind::Identifier, TokenKind::EqOp, TokenKind::NumberLiteral, TokenKind::SemiColon,
+            TokenKind::Identifier, TokenKind::EqEqOp, TokenKind::NumberLiteral, TokenKind::SemiColon,
+            TokenKind::Identifier, TokenKind::NotEqOp, TokenKind::NumberLiteral, TokenKind::SemiColon,
+            TokenKind::Identifier, TokenKind::EqEqOp, TokenKind::EqOp, TokenKind::NumberLiteral, TokenKind::EOF
+        ]);
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        let tokens = Tokenizer::new("a && b || !c", false).tokenize();
+        assert_eq!(kinds(&tokens), vec![
+            TokenKind::Identifier, TokenKind::AndOp, TokenKind::Identifier, TokenKind::OrOp,
+            TokenKind::NegationOp, TokenKind::Identifier, TokenKind::EOF
+        ]);
+        assert_eq!(tokenize_error("a &"), "Unexpected token '&' at position 2: did you mean '&&'?");
+    }
+
+    #[test]
+    fn test_operator_table() {
+        for (lexeme, token) in OPERATORS.iter() {
+            let tokens = Tokenizer::new(lexeme, false).tokenize();
+            assert_eq!(kinds(&tokens), vec![token.kind(), TokenKind::EOF], "{}", lexeme);
+        }
+        for (i, (lexeme, _)) in OPERATORS.iter().enumerate() {
+            assert!(OPERATORS[..i].iter().all(|(earlier, _)| !lexeme.starts_with(earlier)), "{} is shadowed", lexeme);
+        }
+    }
+
+    #[test]
+    fn test_adjacent_operators() {
+        use TokenKind::*;
+        let cases = [
+            ("a<=b", vec![Identifier, LtEqOp, Identifier]),
+            ("a>=b", vec![Identifier, GtEqOp, Identifier]),
+            ("a<b", vec![Identifier, LtOp, Identifier]),
+            ("a==-b", vec![Identifier, EqEqOp, SubOp, Identifier]),
+            ("a=!b", vec![Identifier, EqOp, NegationOp, Identifier]),
+            ("!!a", vec![NegationOp, NegationOp, Identifier]),
+            ("a!==b", vec![Identifier, NotEqOp, EqOp, Identifier]),
+            ("a===b", vec![Identifier, EqEqOp, EqOp, Identifier]),
+            ("a??b?c:d", vec![Identifier, NullishOp, Identifier, QuestionMark, Identifier, Colon, Identifier]),
+            ("a&&!b||c", vec![Identifier, AndOp, NegationOp, Identifier, OrOp, Identifier]),
+            ("2^-1%3/4*5", vec![NumberLiteral, PowOp, SubOp, NumberLiteral, ModOp, NumberLiteral, DivOp, NumberLiteral, MulOp, NumberLiteral]),
+            ("a/b//c", vec![Identifier, DivOp, Identifier]),
+        ];
+        for (source, mut expected) in cases {
+            expected.push(EOF);
+            assert_eq!(kinds(&Tokenizer::new(source, false).tokenize()), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_spread() {
+        let tokens = Tokenizer::new("f(...xs, a.b, .5)", false).tokenize();
+        assert_eq!(kinds(&tokens), vec![
+            TokenKind::Identifier, TokenKind::LeftParen, TokenKind::Spread, TokenKind::Identifier, TokenKind::Comma,
+            TokenKind::Identifier, TokenKind::Dot, TokenKind::Identifier, TokenKind::Comma,
+            TokenKind::NumberLiteral, TokenKind::RightParen, TokenKind::EOF
+        ]);
+    }
+
+    #[test]
+    fn test_string_line_continuation() {
+        let tokens = Tokenizer::new("\"a long \\\n    line\"; \"crlf \\\r\nend\";", false).tokenize();
+        assert_eq!(tokens[0].as_string(), "a long     line");
+        assert_eq!(tokens[2].as_string(), "crlf end");
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let tokens = Tokenizer::new(r#""tab\tnew\nline \"q\" \\ \'" 'it\'s'"#, false).tokenize();
+        assert_eq!(tokens[0].as_string(), "tab\tnew\nline \"q\" \\ '");
+        assert_eq!(tokens[1].as_string(), "it's");
+        assert_eq!(tokenize_error(r#""bad \q""#), "Unknown escape sequence '\\q' at position 5");
+    }
+
+    #[test]
+    fn test_tokenize_large_script_is_fast() {
+        let line = "let total_value = [1.5, 2, \"text\"] + count * (3 - 4) / 2; // note\n";
+        let script = line.repeat(10 * 1024 / line.len() + 1);
+        let start = std::time::Instant::now();
+        let tokens = Tokenizer::new(&script, false).tokenize();
+        let elapsed = start.elapsed();
+        assert!(tokens.len() > 2000);
+        // every character used to recompile a handful of regexes, which took
+        // several seconds for a script of this size
+        assert!(elapsed.as_millis() < 500, "tokenizing 10KB took {:?}", elapsed);
+    }
+
+    #[test]
+    fn test_surrounding_whitespace() {
+        for text in ["", "  ", "\n\t \r\n"] {
+            assert_eq!(kinds(&Tokenizer::new(text, false).tokenize()), vec![TokenKind::EOF]);
+        }
+        let tokens = Tokenizer::new("\n  x;  \n", false).tokenize();
+        assert_eq!(kinds(&tokens), vec![TokenKind::Identifier, TokenKind::SemiColon, TokenKind::EOF]);
+    }
+
+    #[test]
+    fn test_error_position_counts_leading_whitespace() {
+        assert_eq!(tokenize_error("a #"), "Unexpected token '#' at position 2");
+        assert_eq!(tokenize_error("\n\n  a #"), "Unexpected token '#' at position 6");
+    }
+
+    #[test]
+    fn test_crlf_line_numbers() {
+        let lf = "let a = 1;\n// note\nlet b = 2;\n\nlet c = #;";
+        let crlf = lf.replace('\n', "\r\n");
+        let error_line = |source: &str| match catch(|| Tokenizer::new(source, false).tokenize()) {
+            Err(error @ RuntimeError::Syntax { .. }) => line_column(source, error.pos().unwrap()),
+            result => panic!("Expected a tokenizer error, got {:?}", result)
+        };
+        assert_eq!(error_line(lf), (5, 9));
+        assert_eq!(error_line(&crlf), error_line(lf));
+
+        let comment_line = |source: &str| match &Tokenizer::new(source, true).tokenize()[5] {
+            Token::Comment { span, .. } => line_column(source, span.start as usize),
+            token => panic!("Expected a comment, got {:?}", token)
+        };
+        let (lf, crlf) = ("let a = 1;\n// note\n", "let a = 1;\r\n// note\r\n");
+        assert_eq!(comment_line(lf), (2, 1));
+        assert_eq!(comment_line(crlf), (2, 1));
+    }
+}
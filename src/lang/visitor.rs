@@ -0,0 +1,256 @@
+use std::rc::Rc;
+
+use super::parser::ASTNode;
+
+/// A read-only pass over an AST. Every `visit_*` method handles one kind of
+/// node and by default just walks into its children, so a pass only
+/// overrides the methods for the nodes it cares about. An overriding method
+/// calls `walk(self, node)` itself to keep descending.
+pub trait Visitor {
+    fn visit_all(&mut self, nodes: &[Rc<ASTNode>]) {
+        nodes.iter().for_each(|node| self.visit_node(node));
+    }
+
+    /// Dispatches `node` to the `visit_*` method of its kind.
+    fn visit_node(&mut self, node: &ASTNode) {
+        match node {
+            ASTNode::Number(..) => self.visit_number(node),
+            ASTNode::Bool(..) => self.visit_bool(node),
+            ASTNode::Null => self.visit_null(node),
+            ASTNode::String(..) => self.visit_string(node),
+            ASTNode::ArrayLiteral { .. } => self.visit_array_literal(node),
+            ASTNode::ObjectLiteral { .. } => self.visit_object_literal(node),
+            ASTNode::Identifier { .. } => self.visit_identifier(node),
+            ASTNode::FunctionCall { .. } => self.visit_function_call(node),
+            ASTNode::MethodCall { .. } => self.visit_method_call(node),
+            ASTNode::Index { .. } => self.visit_index(node),
+            ASTNode::BinaryExpression { .. } => self.visit_binary_expression(node),
+            ASTNode::UnaryExpression { .. } => self.visit_unary_expression(node),
+            ASTNode::Spread { .. } => self.visit_spread(node),
+            ASTNode::ConditionalExpression { .. } => self.visit_conditional_expression(node),
+            ASTNode::VarDeclaration { .. } => self.visit_var_declaration(node),
+            ASTNode::VarAssignment { .. } => self.visit_var_assignment(node),
+            ASTNode::IfStmt { .. } => self.visit_if_stmt(node),
+            ASTNode::WhileStmt { .. } => self.visit_while_stmt(node),
+            ASTNode::DoWhileStmt { .. } => self.visit_do_while_stmt(node),
+            ASTNode::ForStmt { .. } => self.visit_for_stmt(node),
+            ASTNode::TryCatch { .. } => self.visit_try_catch(node),
+            ASTNode::FunctionDeclaration { .. } => self.visit_function_declaration(node),
+            ASTNode::ReturnStmt { .. } => self.visit_return_stmt(node),
+            ASTNode::Break { .. } => self.visit_break(node),
+            ASTNode::Continue { .. } => self.visit_continue(node),
+            ASTNode::Throw { .. } => self.visit_throw(node),
+        }
+    }
+
+    fn visit_number(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_bool(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_null(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_string(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_array_literal(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_object_literal(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_identifier(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_function_call(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_method_call(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_index(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_binary_expression(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_unary_expression(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_spread(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_conditional_expression(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_var_declaration(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_var_assignment(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_if_stmt(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_while_stmt(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_do_while_stmt(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_for_stmt(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_try_catch(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_function_declaration(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_return_stmt(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_throw(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_break(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+
+    fn visit_continue(&mut self, node: &ASTNode) {
+        walk(self, node);
+    }
+}
+
+/// Visits the children of `node` in source order. Param defaults count as
+/// children of their function declaration.
+pub fn walk<V: Visitor + ?Sized>(visitor: &mut V, node: &ASTNode) {
+    match node {
+        ASTNode::Number(..) | ASTNode::Bool(..) | ASTNode::Null | ASTNode::String(..) | ASTNode::Identifier { .. }
+        | ASTNode::Break { .. } | ASTNode::Continue { .. } => {},
+        ASTNode::ArrayLiteral { elements } => visitor.visit_all(elements),
+        ASTNode::ObjectLiteral { entries } => entries.iter().for_each(|(_, value)| visitor.visit_node(value)),
+        ASTNode::FunctionCall { args, .. } => visitor.visit_all(args),
+        ASTNode::MethodCall { receiver, args, .. } => {
+            visitor.visit_node(receiver);
+            visitor.visit_all(args);
+        },
+        ASTNode::BinaryExpression { left, right, .. } | ASTNode::Index { target: left, index: right } => {
+            visitor.visit_node(left);
+            visitor.visit_node(right);
+        },
+        ASTNode::UnaryExpression { expr, .. } | ASTNode::Spread { expr } => visitor.visit_node(expr),
+        ASTNode::ConditionalExpression { condition, when_true, when_false } => {
+            visitor.visit_node(condition);
+            visitor.visit_node(when_true);
+            visitor.visit_node(when_false);
+        },
+        ASTNode::VarDeclaration { value, .. } | ASTNode::VarAssignment { value, .. } | ASTNode::Throw { value } => visitor.visit_node(value),
+        ASTNode::IfStmt { expr, true_block, false_block } => {
+            visitor.visit_node(expr);
+            visitor.visit_all(true_block);
+            if let Some(false_block) = false_block {
+                visitor.visit_all(false_block);
+            }
+        },
+        ASTNode::WhileStmt { condition, body, .. } => {
+            visitor.visit_node(condition);
+            visitor.visit_all(body);
+        },
+        ASTNode::DoWhileStmt { body, condition, .. } => {
+            visitor.visit_all(body);
+            visitor.visit_node(condition);
+        },
+        ASTNode::ForStmt { init, condition, update, body, .. } => {
+            init.iter().chain(condition).chain(update).for_each(|node| visitor.visit_node(node));
+            visitor.visit_all(body);
+        },
+        ASTNode::TryCatch { try_block, catch_block, .. } => {
+            visitor.visit_all(try_block);
+            visitor.visit_all(catch_block);
+        },
+        ASTNode::FunctionDeclaration { params, body, .. } => {
+            params.iter().filter_map(|param| param.default.as_ref()).for_each(|default| visitor.visit_node(default));
+            visitor.visit_all(body);
+        },
+        ASTNode::ReturnStmt { value } => {
+            if let Some(value) = value {
+                visitor.visit_node(value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lang::parser::Parser;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CallCounter {
+        calls: Vec<String>
+    }
+
+    impl Visitor for CallCounter {
+        fn visit_function_call(&mut self, node: &ASTNode) {
+            if let ASTNode::FunctionCall { name, .. } = node {
+                self.calls.push(name.to_owned());
+            }
+            walk(self, node);
+        }
+    }
+
+    #[test]
+    fn test_count_function_calls() {
+        let ast = Parser::new(r#"
+            fn greet(name = upper("x")) { print(concat("hi ", name)); }
+            let n = len([1, 2]) > 1 ? abs(-1) : 0;
+            for (let i = 0; i < n; i = inc(i)) { greet(); }
+            try { throw fail(); } catch (e) { print(e); }
+        "#).parse();
+        let mut counter = CallCounter::default();
+        counter.visit_all(&ast);
+        assert_eq!(counter.calls, vec!["upper", "print", "concat", "len", "abs", "inc", "greet", "fail", "print"]);
+    }
+
+    #[test]
+    fn test_overriding_without_walking_skips_children() {
+        struct TopLevelCalls(usize);
+        impl Visitor for TopLevelCalls {
+            fn visit_function_call(&mut self, _node: &ASTNode) {
+                self.0 += 1;
+            }
+        }
+        let ast = Parser::new("f(g(h())); if (true) { k(); }").parse();
+        let mut counter = TopLevelCalls(0);
+        counter.visit_all(&ast);
+        assert_eq!(counter.0, 2);
+    }
+}
@@ -1,7 +1,21 @@
-mod lang;
-use lang::tokenizer;
+use std::{env, fs, io, path::PathBuf, process::ExitCode};
 
-fn main() {
-    let mut tokenizer = tokenizer::Tokenizer::new("hola como estas true false 16 5 \"un string literal\"");
-    println!("{:#?}", tokenizer.tokenize())
+use interpreter::lang::{interpreter::Interpreter, repl::Repl};
+
+fn main() -> io::Result<ExitCode> {
+    let Some(path) = env::args().nth(1) else {
+        Repl::default().run(io::stdin().lock(), &mut io::stdout())?;
+        return Ok(ExitCode::SUCCESS);
+    };
+    let source = fs::read_to_string(&path)?;
+    let path = PathBuf::from(path);
+    let mut interpreter = Interpreter::new();
+    interpreter.set_script_path(Some(fs::canonicalize(&path).unwrap_or(path)));
+    match interpreter.run_checked(&source) {
+        Ok(..) => Ok(ExitCode::SUCCESS),
+        Err(error) => {
+            eprintln!("{}", error.report(&source));
+            Ok(ExitCode::FAILURE)
+        }
+    }
 }
@@ -0,0 +1,15 @@
+use std::{env, fs, process::Command};
+
+#[test]
+fn test_script_dir_of_running_file() {
+    let dir = env::temp_dir().join("script_path_test");
+    fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("main.rs_script");
+    fs::write(&script, "print(scriptDir());\nprint(scriptPath());").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_interpreter")).arg(&script).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let dir = fs::canonicalize(&dir).unwrap();
+    let expected = format!("{}\n{}\n", dir.display(), dir.join("main.rs_script").display());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+}